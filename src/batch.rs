@@ -0,0 +1,596 @@
+//! Inserting and removing many values at once.
+
+use crate::{Arena, ArenaId, Change, Slot, State};
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
+
+impl<T> Arena<T> {
+    /// Inserts all values from the iterator into the arena, returning their IDs
+    /// in the same order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let ids = arena.insert_many(['A', 'B', 'C']);
+    ///
+    /// assert_eq!(ids.len(), 3);
+    /// assert_eq!(arena[ids[0]], 'A');
+    /// assert_eq!(arena[ids[2]], 'C');
+    /// ```
+    pub fn insert_many<I: IntoIterator<Item = T>>(&mut self, iter: I) -> Vec<ArenaId<T>> {
+        self.extend_with_ids(iter).into_iter().collect()
+    }
+
+    /// Inserts all values from the iterator into the arena, returning their IDs
+    /// in the same order.
+    ///
+    /// Capacity for the values is reserved up front. If the arena has no free
+    /// slots, the values are given new slots with consecutive indices and uids,
+    /// and their IDs are returned as a compact [`IdRange`] rather than a [`Vec`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::{Arena, InsertedIds};
+    /// let mut arena = Arena::new();
+    /// let ids = arena.extend_with_ids(0..10_000);
+    ///
+    /// assert!(matches!(ids, InsertedIds::Range(_)));
+    /// assert_eq!(ids.len(), 10_000);
+    /// assert_eq!(arena[ids.get(1234).unwrap()], 1234);
+    ///
+    /// // removing values leaves free slots to be reused
+    /// arena.remove(ids.get(0).unwrap());
+    /// let ids = arena.extend_with_ids([-1, -2]);
+    ///
+    /// assert!(matches!(ids, InsertedIds::Ids(_)));
+    /// assert_eq!(arena[ids.get(1).unwrap()], -2);
+    /// ```
+    pub fn extend_with_ids<I: IntoIterator<Item = T>>(&mut self, iter: I) -> InsertedIds<T> {
+        let iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        self.values.reserve(lower);
+
+        if self.first_free.is_some() {
+            let ids = iter.map(|val| self.insert(val)).collect();
+            return InsertedIds::Ids(ids);
+        }
+
+        // with no free slots, every value gets the next new slot and uid
+        self.slots.reserve(lower);
+        let start_idx = self.slots.len();
+        let start_uid = self.next_uid;
+        for val in iter {
+            let idx = self.slots.len();
+            let value = self.values.len();
+            self.slots_push(Slot {
+                value_slot: 0,
+                state: State::Used {
+                    uid: self.next_uid,
+                    value,
+                },
+            });
+            self.slot_mut(value).value_slot = idx;
+            self.values_push(val);
+            self.log_change(|arena| Change::Inserted(arena.slot_ids().id(idx)));
+            self.next_uid += 1;
+        }
+        self.debug_check();
+
+        InsertedIds::Range(IdRange {
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+            start_uid,
+            start_idx,
+            len: self.slots.len() - start_idx,
+            _ty: PhantomData,
+        })
+    }
+
+    /// Removes the values assigned to the IDs from the arena, returning them in
+    /// the same order as the IDs.
+    ///
+    /// Stale IDs, and all but the first of any duplicate IDs, return `None`.
+    /// Unlike repeated calls to [`remove`](Arena::remove), the remaining values
+    /// keep their relative order, and are compacted in a single pass.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    /// let b = arena.insert('B');
+    /// let c = arena.insert('C');
+    /// let d = arena.insert('D');
+    ///
+    /// assert_eq!(
+    ///     arena.remove_many(&[c, a, c]),
+    ///     vec![Some('C'), Some('A'), None]
+    /// );
+    /// assert_eq!(arena.as_slice(), &['B', 'D']);
+    /// assert_eq!(arena.get(b), Some(&'B'));
+    /// assert_eq!(arena.get(d), Some(&'D'));
+    ///
+    /// assert_eq!(arena.remove_many(&[a]), vec![None]);
+    /// ```
+    pub fn remove_many(&mut self, ids: &[ArenaId<T>]) -> Vec<Option<T>> {
+        self.remove_many_with(ids, |_, _| {})
+    }
+
+    /// Removes the values assigned to the IDs from the arena, like
+    /// [`remove_many`](Arena::remove_many), calling `on_swap` with the two
+    /// positions every time two values are swapped.
+    ///
+    /// The remaining values are swapped to the front of the arena, and the
+    /// removed ones are truncated off its end. Applying the same swaps to
+    /// anything indexed by position, then truncating it to the arena's new
+    /// length, keeps it in sync with the arena.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from(['A', 'B', 'C', 'D']);
+    /// let ids: Vec<_> = arena.ids().collect();
+    /// let mut mirror = vec!['a', 'b', 'c', 'd'];
+    ///
+    /// let removed = arena.remove_many_with(&[ids[0], ids[2]], |i, j| mirror.swap(i, j));
+    /// mirror.truncate(arena.len());
+    ///
+    /// assert_eq!(removed, vec![Some('A'), Some('C')]);
+    /// assert_eq!(arena.as_slice(), &['B', 'D']);
+    /// assert_eq!(mirror, ['b', 'd']);
+    /// ```
+    pub fn remove_many_with<S>(&mut self, ids: &[ArenaId<T>], on_swap: S) -> Vec<Option<T>>
+    where
+        S: FnMut(usize, usize),
+    {
+        let mut removed: Vec<_> = ids.iter().map(|_| None).collect();
+        self.take_many(ids, on_swap, |i, val| removed[i] = Some(val));
+        removed
+    }
+
+    /// Drops the values assigned to the IDs from the arena, returning how many
+    /// were removed.
+    ///
+    /// This is the same as [`remove_many`](Arena::remove_many), but doesn't
+    /// collect the removed values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from(['A', 'B', 'C', 'D']);
+    /// let ids: Vec<_> = arena.ids().collect();
+    ///
+    /// assert_eq!(arena.delete_many(&[ids[0], ids[2], ids[0]]), 2);
+    /// assert_eq!(arena.as_slice(), &['B', 'D']);
+    /// ```
+    pub fn delete_many(&mut self, ids: &[ArenaId<T>]) -> usize {
+        self.delete_many_with(ids, |_, _| {})
+    }
+
+    /// Drops the values assigned to the IDs from the arena, like
+    /// [`delete_many`](Arena::delete_many), calling `on_swap` with the two
+    /// positions every time two values are swapped, like
+    /// [`remove_many_with`](Arena::remove_many_with).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from(['A', 'B', 'C', 'D']);
+    /// let ids: Vec<_> = arena.ids().collect();
+    /// let mut mirror = vec!['a', 'b', 'c', 'd'];
+    ///
+    /// assert_eq!(arena.delete_many_with(&[ids[1]], |i, j| mirror.swap(i, j)), 1);
+    /// mirror.truncate(arena.len());
+    ///
+    /// assert_eq!(arena.as_slice(), &['A', 'C', 'D']);
+    /// assert_eq!(mirror, ['a', 'c', 'd']);
+    /// ```
+    pub fn delete_many_with<S>(&mut self, ids: &[ArenaId<T>], on_swap: S) -> usize
+    where
+        S: FnMut(usize, usize),
+    {
+        let mut count = 0;
+        self.take_many(ids, on_swap, |_, _| count += 1);
+        count
+    }
+
+    // Removes the values, passing each one to `removed` with the index of its ID.
+    fn take_many<S, F>(&mut self, ids: &[ArenaId<T>], mut on_swap: S, mut removed: F)
+    where
+        S: FnMut(usize, usize),
+        F: FnMut(usize, T),
+    {
+        // mark the position of every live value with the index of its ID
+        let mut marks = vec![None; self.values.len()];
+        let mut count = 0;
+        for (i, &id) in ids.iter().enumerate() {
+            if let Some(pos) = self.index_of(id) {
+                if marks[pos].is_none() {
+                    marks[pos] = Some(i);
+                    count += 1;
+                }
+            }
+        }
+        if count == 0 {
+            return;
+        }
+
+        // move the surviving values to the front, keeping their order, which
+        // leaves the removed values at the end
+        let mut write = 0;
+        for read in 0..marks.len() {
+            if marks[read].is_none() {
+                if write != read {
+                    self.swap_values(write, read);
+                    marks.swap(write, read);
+                    on_swap(write, read);
+                }
+                write += 1;
+            }
+        }
+
+        // then pop them off and free their slots
+        while let Some(i) = marks.pop().flatten() {
+            removed(i, self.pop_value().unwrap());
+        }
+        self.debug_check();
+    }
+
+    /// Retains only the values specified by the predicate, removing the others.
+    ///
+    /// The remaining values keep their relative order and their IDs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert(1);
+    /// let b = arena.insert(2);
+    /// let c = arena.insert(3);
+    ///
+    /// arena.retain(|val| val % 2 == 1);
+    ///
+    /// assert_eq!(arena.as_slice(), &[1, 3]);
+    /// assert_eq!(arena.get(a), Some(&1));
+    /// assert_eq!(arena.get(b), None);
+    /// assert_eq!(arena.get(c), Some(&3));
+    /// ```
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, keep: F) {
+        self.retain_with(keep, |_, _| {});
+    }
+
+    /// Retains only the values specified by the predicate, like [`retain`](Arena::retain),
+    /// calling `on_swap` with the two positions every time two values are
+    /// swapped.
+    ///
+    /// The values that are kept are swapped to the front of the arena, and the
+    /// rest are truncated off its end. Applying the same swaps to anything
+    /// indexed by position, then truncating it to the arena's new length, keeps
+    /// it in sync with the arena.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from([1, 2, 3, 4]);
+    /// let mut mirror = vec!['a', 'b', 'c', 'd'];
+    ///
+    /// arena.retain_with(|val| val % 2 == 0, |i, j| mirror.swap(i, j));
+    /// mirror.truncate(arena.len());
+    ///
+    /// assert_eq!(arena.as_slice(), &[2, 4]);
+    /// assert_eq!(mirror, ['b', 'd']);
+    /// ```
+    pub fn retain_with<F, S>(&mut self, mut keep: F, mut on_swap: S)
+    where
+        F: FnMut(&T) -> bool,
+        S: FnMut(usize, usize),
+    {
+        let mut write = 0;
+        for read in 0..self.values.len() {
+            if keep(&self.values[read]) {
+                if write != read {
+                    self.swap_values(write, read);
+                    on_swap(write, read);
+                }
+                write += 1;
+            }
+        }
+        while self.values.len() > write {
+            self.pop_value();
+        }
+        self.debug_check();
+    }
+}
+
+/// The IDs of values inserted by [`Arena::extend_with_ids`].
+#[derive(Debug)]
+pub enum InsertedIds<T> {
+    /// The values were given new, consecutive slots.
+    Range(IdRange<T>),
+    /// Some of the values reused free slots.
+    Ids(Vec<ArenaId<T>>),
+}
+
+impl<T> InsertedIds<T> {
+    /// Returns the number of IDs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    ///
+    /// assert_eq!(arena.extend_with_ids(['A', 'B']).len(), 2);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Range(range) => range.len(),
+            Self::Ids(ids) => ids.len(),
+        }
+    }
+
+    /// Returns `true` if no values were inserted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::<char>::new();
+    ///
+    /// assert!(arena.extend_with_ids([]).is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the ID of the `i`th inserted value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let ids = arena.extend_with_ids(['A', 'B']);
+    ///
+    /// assert_eq!(arena.get(ids.get(0).unwrap()), Some(&'A'));
+    /// assert_eq!(ids.get(2), None);
+    /// ```
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<ArenaId<T>> {
+        match self {
+            Self::Range(range) => range.get(i),
+            Self::Ids(ids) => ids.get(i).copied(),
+        }
+    }
+}
+
+impl<T> Clone for InsertedIds<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        match self {
+            Self::Range(range) => Self::Range(range.clone()),
+            Self::Ids(ids) => Self::Ids(ids.clone()),
+        }
+    }
+}
+
+impl<T> PartialEq for InsertedIds<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Range(a), Self::Range(b)) => a == b,
+            (Self::Ids(a), Self::Ids(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<T> Eq for InsertedIds<T> {}
+
+impl<T> IntoIterator for InsertedIds<T> {
+    type Item = ArenaId<T>;
+    type IntoIter = InsertedIdsIter<T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        InsertedIdsIter(match self {
+            Self::Range(range) => Iter::Range(range),
+            Self::Ids(ids) => Iter::Ids(ids.into_iter()),
+        })
+    }
+}
+
+/// Iterator over the IDs of values inserted by [`Arena::extend_with_ids`].
+pub struct InsertedIdsIter<T>(Iter<T>);
+
+enum Iter<T> {
+    Range(IdRange<T>),
+    Ids(std::vec::IntoIter<ArenaId<T>>),
+}
+
+impl<T> Iterator for InsertedIdsIter<T> {
+    type Item = ArenaId<T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            Iter::Range(range) => range.next(),
+            Iter::Ids(ids) => ids.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            Iter::Range(range) => range.size_hint(),
+            Iter::Ids(ids) => ids.size_hint(),
+        }
+    }
+}
+
+impl<T> DoubleEndedIterator for InsertedIdsIter<T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            Iter::Range(range) => range.next_back(),
+            Iter::Ids(ids) => ids.next_back(),
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for InsertedIdsIter<T> {}
+
+impl<T> FusedIterator for InsertedIdsIter<T> {}
+
+/// A run of IDs with consecutive slot indices and uids, created by
+/// [`Arena::extend_with_ids`].
+///
+/// Like [`Range`](std::ops::Range), this is an iterator over the IDs it holds.
+pub struct IdRange<T> {
+    #[cfg(feature = "uuid")]
+    uuid: Uuid,
+    start_uid: u64,
+    start_idx: usize,
+    len: usize,
+    _ty: PhantomData<fn() -> T>,
+}
+
+impl<T> IdRange<T> {
+    /// Returns the `i`th ID in the range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::{Arena, InsertedIds};
+    /// let mut arena = Arena::new();
+    /// let InsertedIds::Range(range) = arena.extend_with_ids(['A', 'B']) else {
+    ///     unreachable!()
+    /// };
+    ///
+    /// assert_eq!(arena.get(range.get(1).unwrap()), Some(&'B'));
+    /// assert_eq!(range.get(2), None);
+    /// ```
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<ArenaId<T>> {
+        (i < self.len).then(|| ArenaId {
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+            uid: self.start_uid + i as u64,
+            idx: self.start_idx + i,
+            _ty: PhantomData,
+        })
+    }
+
+    /// Returns `true` if the ID is in the range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::{Arena, InsertedIds};
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    /// let InsertedIds::Range(range) = arena.extend_with_ids(['B', 'C']) else {
+    ///     unreachable!()
+    /// };
+    ///
+    /// assert!(!range.contains(a));
+    /// assert!(range.clone().all(|id| range.contains(id)));
+    /// ```
+    #[inline]
+    pub fn contains(&self, id: ArenaId<T>) -> bool {
+        #[cfg(feature = "uuid")]
+        if id.uuid != self.uuid {
+            return false;
+        }
+        id.idx
+            .checked_sub(self.start_idx)
+            .and_then(|i| self.get(i))
+            .is_some_and(|other| other.uid == id.uid)
+    }
+}
+
+impl<T> Iterator for IdRange<T> {
+    type Item = ArenaId<T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.get(0)?;
+        self.start_uid += 1;
+        self.start_idx += 1;
+        self.len -= 1;
+        Some(id)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let n = n.min(self.len);
+        self.start_uid += n as u64;
+        self.start_idx += n;
+        self.len -= n;
+        self.next()
+    }
+}
+
+impl<T> DoubleEndedIterator for IdRange<T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let id = self.get(self.len.checked_sub(1)?)?;
+        self.len -= 1;
+        Some(id)
+    }
+}
+
+impl<T> ExactSizeIterator for IdRange<T> {}
+
+impl<T> FusedIterator for IdRange<T> {}
+
+impl<T> Clone for IdRange<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+            start_uid: self.start_uid,
+            start_idx: self.start_idx,
+            len: self.len,
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for IdRange<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.get(0) == other.get(0) && self.len == other.len
+    }
+}
+
+impl<T> Eq for IdRange<T> {}
+
+impl<T> std::fmt::Debug for IdRange<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IdRange")
+            .field("start_uid", &self.start_uid)
+            .field("start_idx", &self.start_idx)
+            .field("len", &self.len)
+            .finish()
+    }
+}
@@ -0,0 +1,195 @@
+//! An optional log of the changes made to an arena, for replicating it.
+
+use crate::{Arena, ArenaId};
+use std::ops::Range;
+
+impl<T> Arena<T> {
+    /// Starts or stops recording the changes made to the arena. Stopping also
+    /// discards any changes that haven't been drained yet.
+    ///
+    /// While recording, every value that is inserted, removed, moved to another
+    /// position or mutably accessed is logged as a [`Change`], so that a copy
+    /// of the arena can be kept up to date without comparing the whole arena.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::{Arena, Change};
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    ///
+    /// arena.record_changes(true);
+    /// assert!(arena.is_recording_changes());
+    ///
+    /// let b = arena.insert('B');
+    /// arena[b] = 'C';
+    /// arena.remove(a);
+    ///
+    /// assert_eq!(
+    ///     arena.drain_changes().collect::<Vec<_>>(),
+    ///     [
+    ///         Change::Inserted(b),
+    ///         Change::Modified(b),
+    ///         Change::Removed(a),
+    ///         Change::Moved { id: b, from: 1, to: 0 },
+    ///     ]
+    /// );
+    /// assert!(arena.changes().is_empty());
+    /// ```
+    pub fn record_changes(&mut self, enabled: bool) {
+        self.changes.recording = enabled;
+        if !enabled {
+            self.changes.changes = Vec::new();
+        }
+    }
+
+    /// Returns `true` if the arena is recording its changes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::<char>::new();
+    /// assert!(!arena.is_recording_changes());
+    ///
+    /// arena.record_changes(true);
+    /// assert!(arena.is_recording_changes());
+    /// ```
+    #[inline]
+    pub fn is_recording_changes(&self) -> bool {
+        self.changes.recording
+    }
+
+    /// Returns the changes recorded since they were last drained, oldest first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::{Arena, Change};
+    /// let mut arena = Arena::new();
+    /// arena.record_changes(true);
+    ///
+    /// let a = arena.insert('A');
+    ///
+    /// assert_eq!(arena.changes(), &[Change::Inserted(a)]);
+    /// ```
+    #[inline]
+    pub fn changes(&self) -> &[Change<T>] {
+        &self.changes.changes
+    }
+
+    /// Removes and returns all the recorded changes, oldest first. The log keeps
+    /// its allocation, so draining it regularly is cheap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::{Arena, Change};
+    /// let mut arena = Arena::new();
+    /// arena.record_changes(true);
+    ///
+    /// let a = arena.insert('A');
+    /// assert!(arena.drain_changes().eq([Change::Inserted(a)]));
+    ///
+    /// arena.pop();
+    /// assert!(arena.drain_changes().eq([Change::Removed(a)]));
+    /// assert_eq!(arena.drain_changes().next(), None);
+    /// ```
+    #[inline]
+    pub fn drain_changes(&mut self) -> std::vec::Drain<'_, Change<T>> {
+        self.changes.changes.drain(..)
+    }
+
+    // records the change if recording is enabled, only creating it if it is
+    #[inline]
+    pub(crate) fn log_change<F>(&mut self, change: F)
+    where
+        F: FnOnce(&Self) -> Change<T>,
+    {
+        if self.changes.recording {
+            let change = change(self);
+            self.changes.changes.push(change);
+        }
+    }
+
+    // records the values at the positions as modified
+    pub(crate) fn log_modified(&mut self, positions: Range<usize>) {
+        if self.changes.recording {
+            for pos in positions {
+                let id = self.slot_ids().value_id(pos);
+                self.changes.changes.push(Change::Modified(id));
+            }
+        }
+    }
+}
+
+/// A change made to an arena, recorded while [`Arena::record_changes`] is enabled.
+#[derive(Debug)]
+pub enum Change<T> {
+    /// A value was inserted at the end of the arena.
+    Inserted(ArenaId<T>),
+    /// A value was removed. If it wasn't the last value, this is followed by
+    /// the last value being moved into its position.
+    Removed(ArenaId<T>),
+    /// A value was moved to another position.
+    Moved {
+        id: ArenaId<T>,
+        from: usize,
+        to: usize,
+    },
+    /// A value was assigned a new ID by [`Arena::reissue`], without moving.
+    Reissued { old: ArenaId<T>, new: ArenaId<T> },
+    /// A value was mutably accessed, and so may have been modified.
+    ///
+    /// Calls that give mutable access to many values at once, such as
+    /// [`iter_mut`](Arena::iter_mut), record this for every value they cover.
+    Modified(ArenaId<T>),
+}
+
+impl<T> Clone for Change<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Change<T> {}
+
+impl<T> PartialEq for Change<T> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Inserted(a), Self::Inserted(b)) => a == b,
+            (Self::Removed(a), Self::Removed(b)) => a == b,
+            (
+                Self::Moved { id, from, to },
+                Self::Moved {
+                    id: other_id,
+                    from: other_from,
+                    to: other_to,
+                },
+            ) => (id, from, to) == (other_id, other_from, other_to),
+            (Self::Reissued { old, new }, Self::Reissued { old: a, new: b }) => {
+                (old, new) == (a, b)
+            }
+            (Self::Modified(a), Self::Modified(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<T> Eq for Change<T> {}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ChangeLog<T> {
+    recording: bool,
+    changes: Vec<Change<T>>,
+}
+
+impl<T> ChangeLog<T> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            recording: false,
+            changes: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Buffers of insertions and removals to apply to an arena later, such as
+//! after iterating over it.
+
+use crate::concurrent::PredictedIds;
+use crate::{Arena, ArenaId};
+
+impl<T> Arena<T> {
+    /// Creates an empty command buffer for the arena, which can record changes
+    /// to make to it while it is borrowed, such as during iteration. The changes
+    /// are made by passing the buffer to [`apply`](Arena::apply).
+    ///
+    /// The arena must not be modified in between, other than through mutable
+    /// access to its values, since the buffer predicts the IDs it will assign
+    /// to inserted values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from([1, 2, 3, 4]);
+    /// let mut commands = arena.commands();
+    ///
+    /// for (id, val) in arena.pairs_mut() {
+    ///     if *val % 2 == 0 {
+    ///         commands.remove(id);
+    ///     } else {
+    ///         *val *= 10;
+    ///         commands.insert(*val + 1);
+    ///     }
+    /// }
+    ///
+    /// arena.apply(commands);
+    /// assert_eq!(arena.as_slice(), &[10, 11, 30, 31]);
+    /// ```
+    pub fn commands(&self) -> ArenaCommands<T> {
+        ArenaCommands {
+            commands: Vec::new(),
+            inserted: 0,
+            predicted: PredictedIds::of(self),
+        }
+    }
+
+    /// Applies the changes recorded in the command buffer, in the order they
+    /// were recorded.
+    ///
+    /// Removing or replacing a value whose ID isn't assigned to one does
+    /// nothing, like [`remove`](Arena::remove) and [`replace`](Arena::replace).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer has inserted values and the arena has been modified
+    /// since the buffer was [created](Arena::commands), other than through
+    /// mutable access to its values, so that the buffer's IDs are no longer the
+    /// ones the arena would assign. This includes applying another buffer that
+    /// was created at the same time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    ///
+    /// let mut commands = arena.commands();
+    /// let b = commands.insert('B');
+    /// commands.replace(b, 'C');
+    /// commands.remove(a);
+    ///
+    /// arena.apply(commands);
+    /// assert_eq!(arena.as_slice(), &['C']);
+    /// assert_eq!(arena.get(b), Some(&'C'));
+    /// ```
+    pub fn apply(&mut self, commands: ArenaCommands<T>) {
+        if commands.inserted > 0 {
+            assert!(
+                commands.predicted.matches(self),
+                "arena was modified after the command buffer was created"
+            );
+            // reserve the predicted IDs, so that any of them can be filled first
+            for _ in 0..commands.inserted {
+                self.reserve_id();
+            }
+        }
+        for command in commands.commands {
+            match command {
+                Command::Insert(id, value) => {
+                    let filled = self.fill(id, value);
+                    assert!(filled.is_ok(), "command buffer's ID was not reserved");
+                }
+                Command::Remove(id) => {
+                    self.remove(id);
+                }
+                Command::Replace(id, value) => {
+                    self.replace(id, value);
+                }
+            }
+        }
+    }
+}
+
+/// A buffer of insertions and removals to make to an arena, recorded while the
+/// arena is borrowed and made later by [`Arena::apply`].
+///
+/// This is created by the [`commands`](Arena::commands) method on [`Arena`].
+#[derive(Debug)]
+pub struct ArenaCommands<T> {
+    commands: Vec<Command<T>>,
+    inserted: usize,
+    predicted: PredictedIds,
+}
+
+#[derive(Debug)]
+enum Command<T> {
+    Insert(ArenaId<T>, T),
+    Remove(ArenaId<T>),
+    Replace(ArenaId<T>, T),
+}
+
+impl<T> ArenaCommands<T> {
+    /// Records inserting a value, returning the ID it will be assigned. The ID
+    /// can be used in later commands, and is valid once the buffer is applied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let mut commands = arena.commands();
+    /// let a = commands.insert('A');
+    /// assert_eq!(arena.get(a), None);
+    ///
+    /// arena.apply(commands);
+    /// assert_eq!(arena.get(a), Some(&'A'));
+    /// ```
+    pub fn insert(&mut self, value: T) -> ArenaId<T> {
+        let id = self.predicted.nth(self.inserted);
+        self.inserted += 1;
+        self.commands.push(Command::Insert(id, value));
+        id
+    }
+
+    /// Records removing the value assigned to the ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    ///
+    /// let mut commands = arena.commands();
+    /// commands.remove(a);
+    /// assert!(arena.contains(a));
+    ///
+    /// arena.apply(commands);
+    /// assert!(!arena.contains(a));
+    /// ```
+    pub fn remove(&mut self, id: ArenaId<T>) {
+        self.commands.push(Command::Remove(id));
+    }
+
+    /// Records replacing the value assigned to the ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    ///
+    /// let mut commands = arena.commands();
+    /// commands.replace(a, 'Z');
+    ///
+    /// arena.apply(commands);
+    /// assert_eq!(arena.get(a), Some(&'Z'));
+    /// ```
+    pub fn replace(&mut self, id: ArenaId<T>, value: T) {
+        self.commands.push(Command::Replace(id, value));
+    }
+
+    /// Returns the number of recorded commands.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let arena = Arena::<char>::new();
+    /// let mut commands = arena.commands();
+    /// commands.insert('A');
+    /// commands.insert('B');
+    ///
+    /// assert_eq!(commands.len(), 2);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns `true` if no commands have been recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let arena = Arena::<char>::new();
+    /// assert!(arena.commands().is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
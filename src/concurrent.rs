@@ -0,0 +1,271 @@
+//! An arena that can reserve IDs from many threads at once.
+
+use crate::{Arena, ArenaId};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+/// An arena whose IDs can be [reserved](ConcurrentArena::reserve_id) through a
+/// shared reference, so that many threads can reserve them at once.
+///
+/// Reserving an ID only bumps an atomic counter to take the next free slot
+/// from a list of the arena's free slots, which is collected when the arena is
+/// created or [flushed](ConcurrentArena::flush). The reservations only become
+/// part of the arena when it is flushed, after which they can be
+/// [filled](Arena::fill) like any other reserved IDs. This is how ECSs let
+/// parallel systems spawn entities, with the spawns applied once the systems
+/// are done.
+///
+/// The arena can be accessed through [`Deref<Target = Arena<T>>`](Deref) and
+/// [`DerefMut`]. Mutable access reserves the pending IDs in the arena first,
+/// and invalidates the list of free slots, since the arena may change. Until
+/// the arena is flushed again, the first reservation collects the free slots
+/// while any other reserving threads wait for it, so flush the arena after
+/// modifying it to keep reservations from blocking.
+///
+/// # Examples
+///
+/// ```
+/// # use arena::ConcurrentArena;
+/// let mut arena = ConcurrentArena::new();
+///
+/// let ids: Vec<_> = std::thread::scope(|s| {
+///     let workers: Vec<_> = (0..4)
+///         .map(|_| s.spawn(|| arena.reserve_id()))
+///         .collect();
+///     workers.into_iter().map(|w| w.join().unwrap()).collect()
+/// });
+///
+/// arena.flush();
+/// for (i, &id) in ids.iter().enumerate() {
+///     arena.fill(id, i).unwrap();
+/// }
+/// assert_eq!(arena.len(), 4);
+/// ```
+pub struct ConcurrentArena<T> {
+    arena: Arena<T>,
+    // collected when the arena is created or flushed, or when first reserving
+    // after it is mutably accessed
+    predicted: OnceLock<PredictedIds>,
+    pending: AtomicUsize,
+}
+
+impl<T> ConcurrentArena<T> {
+    /// Creates a new, empty arena.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ConcurrentArena;
+    /// let arena = ConcurrentArena::<char>::new();
+    /// assert!(arena.is_empty());
+    /// ```
+    #[inline]
+    pub fn new() -> Self {
+        Self::from(Arena::new())
+    }
+
+    /// Reserves an ID for a value that will be added after the arena is
+    /// [flushed](ConcurrentArena::flush).
+    ///
+    /// Until then, the arena doesn't know about the reservation, so it isn't
+    /// [reserved](Arena::is_reserved) yet, but no other reservation will get
+    /// the same ID.
+    ///
+    /// This never waits for other threads, unless the arena has been mutably
+    /// accessed since it was last flushed, in which case the first reservation
+    /// collects its free slots first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ConcurrentArena;
+    /// let mut arena = ConcurrentArena::new();
+    /// let a = arena.reserve_id();
+    /// assert!(!arena.is_reserved(a));
+    ///
+    /// arena.flush();
+    /// assert!(arena.is_reserved(a));
+    ///
+    /// arena.fill(a, 'A').unwrap();
+    /// assert_eq!(arena.get(a), Some(&'A'));
+    /// ```
+    pub fn reserve_id(&self) -> ArenaId<T> {
+        let n = self.pending.fetch_add(1, Ordering::Relaxed);
+        self.predicted
+            .get_or_init(|| PredictedIds::of(&self.arena))
+            .nth(n)
+    }
+
+    /// Returns the number of IDs reserved since the arena was last flushed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ConcurrentArena;
+    /// let mut arena = ConcurrentArena::<char>::new();
+    /// arena.reserve_id();
+    /// arena.reserve_id();
+    /// assert_eq!(arena.pending_count(), 2);
+    ///
+    /// arena.flush();
+    /// assert_eq!(arena.pending_count(), 0);
+    /// ```
+    #[inline]
+    pub fn pending_count(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    /// Adds the IDs reserved since the arena was last flushed to the arena as
+    /// [reserved](Arena::is_reserved) IDs, so that they can be
+    /// [filled](Arena::fill).
+    ///
+    /// This also collects the arena's free slots for the next reservations, if
+    /// they have changed, so that reserving doesn't have to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ConcurrentArena;
+    /// let mut arena = ConcurrentArena::new();
+    /// let a = arena.reserve_id();
+    /// let b = arena.reserve_id();
+    /// arena.flush();
+    ///
+    /// arena.fill(b, 'B').unwrap();
+    /// arena.cancel_reservation(a);
+    /// assert_eq!(arena.as_slice(), &['B']);
+    /// ```
+    pub fn flush(&mut self) {
+        self.reserve_pending();
+        if !self.predicted.get().is_some_and(|p| p.matches(&self.arena)) {
+            self.predicted = OnceLock::from(PredictedIds::of(&self.arena));
+        }
+    }
+
+    // reserves the pending IDs in the arena, leaving the predicted IDs stale
+    fn reserve_pending(&mut self) {
+        let pending = std::mem::take(self.pending.get_mut());
+        for _ in 0..pending {
+            self.arena.reserve_id();
+        }
+    }
+
+    /// Flushes the arena, then returns it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ConcurrentArena;
+    /// let arena = ConcurrentArena::<char>::new();
+    /// let a = arena.reserve_id();
+    ///
+    /// let arena = arena.into_inner();
+    /// assert!(arena.is_reserved(a));
+    /// ```
+    pub fn into_inner(mut self) -> Arena<T> {
+        self.reserve_pending();
+        self.arena
+    }
+}
+
+// The IDs that `Arena::reserve_id` will return next, predicted from the arena's
+// state, so that they can be handed out before the reservations are made.
+#[derive(Debug)]
+pub(crate) struct PredictedIds {
+    // the free slots, which are reserved first, in the order they will be
+    // reserved in
+    free: Vec<usize>,
+    slot_count: usize,
+    next_uid: u64,
+    #[cfg(feature = "uuid")]
+    uuid: uuid::Uuid,
+}
+
+impl PredictedIds {
+    pub(crate) fn of<T>(arena: &Arena<T>) -> Self {
+        Self {
+            free: arena.free_list().collect(),
+            slot_count: arena.slots.len(),
+            next_uid: arena.next_uid,
+            #[cfg(feature = "uuid")]
+            uuid: arena.uuid,
+        }
+    }
+
+    // the ID of the nth reservation
+    pub(crate) fn nth<T>(&self, n: usize) -> ArenaId<T> {
+        let idx = match self.free.get(n) {
+            Some(&idx) => idx,
+            None => self.slot_count + (n - self.free.len()),
+        };
+        ArenaId {
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+            uid: self.next_uid + n as u64,
+            idx,
+            _ty: PhantomData,
+        }
+    }
+
+    // Returns `true` if the arena will still make the predicted reservations.
+    // Any change to the arena's slots either issues a uid or frees a slot,
+    // which changes the head of the free list, and the IDs also hold the
+    // arena's UUID, which a patch can change.
+    pub(crate) fn matches<T>(&self, arena: &Arena<T>) -> bool {
+        #[cfg(feature = "uuid")]
+        if arena.uuid != self.uuid {
+            return false;
+        }
+        arena.next_uid == self.next_uid
+            && arena.slots.len() == self.slot_count
+            && arena.first_free == self.free.first().copied()
+    }
+}
+
+impl<T> Deref for ConcurrentArena<T> {
+    type Target = Arena<T>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.arena
+    }
+}
+
+impl<T> DerefMut for ConcurrentArena<T> {
+    // the arena may be about to allocate slots, so the reservations have to
+    // be in it first, and the collected free list goes stale
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.reserve_pending();
+        self.predicted.take();
+        &mut self.arena
+    }
+}
+
+impl<T> Default for ConcurrentArena<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<Arena<T>> for ConcurrentArena<T> {
+    #[inline]
+    fn from(arena: Arena<T>) -> Self {
+        Self {
+            predicted: OnceLock::from(PredictedIds::of(&arena)),
+            arena,
+            pending: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for ConcurrentArena<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConcurrentArena")
+            .field("arena", &self.arena)
+            .field("pending", &self.pending_count())
+            .finish()
+    }
+}
@@ -0,0 +1,593 @@
+//! A copy-on-write arena, whose snapshots can be read while it is modified.
+
+use crate::{ArenaId, Slot, State};
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::ops::{Deref, Index};
+use std::sync::Arc;
+
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
+
+/// The number of values or slots stored in each shared chunk.
+const CHUNK_LEN: usize = 64;
+
+/// An arena that stores its values in shared chunks, so that taking a
+/// [`snapshot`](CowArena::snapshot) of it is O(1).
+///
+/// Snapshots share the arena's chunks until the arena modifies them. Then the
+/// arena copies only the chunks it touches, so a snapshot keeps seeing the
+/// arena as it was when it was taken. Since the values are split into chunks,
+/// they can't be accessed as a single slice like an [`Arena`](crate::Arena)'s.
+///
+/// Values are assigned IDs and kept in order just like in an `Arena`: removing
+/// a value moves the last value into its position.
+///
+/// The arena can be read directly, since it implements
+/// [`Deref<Target = CowSnapshot<T>>`](Deref).
+///
+/// # Examples
+///
+/// ```
+/// # use arena::CowArena;
+/// let mut arena = CowArena::new();
+/// let a = arena.insert('A');
+/// let b = arena.insert('B');
+///
+/// let snapshot = arena.snapshot();
+/// arena.remove(a);
+/// arena[b] = 'Z';
+///
+/// // the snapshot still sees the arena as it was when it was taken
+/// assert_eq!(snapshot.get(a), Some(&'A'));
+/// assert!(snapshot.iter().eq(&['A', 'B']));
+/// assert!(arena.iter().eq(&['Z']));
+/// ```
+#[derive(Clone)]
+pub struct CowArena<T> {
+    current: CowSnapshot<T>,
+    next_uid: u64,
+    first_free: Option<usize>,
+}
+
+/// A read-only snapshot of a [`CowArena`], created by [`CowArena::snapshot`].
+///
+/// Snapshots are cheap to clone, and can be sent to and shared between threads
+/// if the values can be, so the arena can keep being modified on one thread
+/// while its snapshots are read on others.
+///
+/// # Examples
+///
+/// ```
+/// # use arena::CowArena;
+/// let mut arena = CowArena::new();
+/// let a = arena.insert(1);
+///
+/// let snapshot = arena.snapshot();
+/// # fn is_send_sync<T: Send + Sync>(_: &T) {}
+/// # is_send_sync(&snapshot);
+/// let reader = std::thread::spawn(move || snapshot.iter().sum::<i32>());
+/// arena[a] = 2;
+///
+/// assert_eq!(reader.join().unwrap(), 1);
+/// ```
+pub struct CowSnapshot<T> {
+    values: Chunks<T>,
+    slots: Chunks<Slot>,
+    #[cfg(feature = "uuid")]
+    uuid: Uuid,
+}
+
+impl<T> CowArena<T> {
+    /// Creates a new, empty arena.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::CowArena;
+    /// let arena = CowArena::<char>::new();
+    /// assert!(arena.is_empty());
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            current: CowSnapshot {
+                values: Chunks::new(),
+                slots: Chunks::new(),
+                #[cfg(feature = "uuid")]
+                uuid: Uuid::new_v4(),
+            },
+            next_uid: 1,
+            first_free: None,
+        }
+    }
+
+    /// Returns a snapshot of the arena as it currently is. This is O(1), since
+    /// the snapshot shares the arena's chunks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::CowArena;
+    /// let mut arena = CowArena::new();
+    /// arena.insert('A');
+    ///
+    /// let snapshot = arena.snapshot();
+    /// arena.insert('B');
+    ///
+    /// assert_eq!(snapshot.len(), 1);
+    /// assert_eq!(arena.len(), 2);
+    /// ```
+    #[inline]
+    pub fn snapshot(&self) -> CowSnapshot<T> {
+        self.current.clone()
+    }
+}
+
+impl<T: Clone> CowArena<T> {
+    /// Adds a value to the arena, returning its ID.
+    ///
+    /// If a snapshot shares the chunk the value is added to, the chunk is copied
+    /// first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::CowArena;
+    /// let mut arena = CowArena::new();
+    /// let a = arena.insert('A');
+    ///
+    /// assert_eq!(arena.get(a), Some(&'A'));
+    /// ```
+    pub fn insert(&mut self, value: T) -> ArenaId<T> {
+        let pos = self.current.values.len;
+        let state = State::Used {
+            uid: self.next_uid,
+            value: pos,
+        };
+        let idx = match self.first_free {
+            Some(idx) => {
+                let slot = self.current.slots.get_mut(idx);
+                match slot.state {
+                    State::Free { next_free } => self.first_free = next_free,
+                    _ => unreachable!(),
+                }
+                slot.state = state;
+                idx
+            }
+            None => {
+                self.current.slots.push(Slot {
+                    value_slot: 0,
+                    state,
+                });
+                self.current.slots.len - 1
+            }
+        };
+        self.current.slots.get_mut(pos).value_slot = idx;
+        self.current.values.push(value);
+        let id = self.current.id(idx, self.next_uid);
+        self.next_uid += 1;
+        id
+    }
+
+    /// Returns a mutable reference to the value assigned to the ID, or `None`
+    /// if it isn't assigned to one.
+    ///
+    /// If a snapshot shares the value's chunk, the chunk is copied first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::CowArena;
+    /// let mut arena = CowArena::new();
+    /// let a = arena.insert('A');
+    ///
+    /// *arena.get_mut(a).unwrap() = 'Z';
+    /// assert_eq!(arena.get(a), Some(&'Z'));
+    /// ```
+    pub fn get_mut(&mut self, id: ArenaId<T>) -> Option<&mut T> {
+        let pos = self.current.position(id)?;
+        Some(self.current.values.get_mut(pos))
+    }
+
+    /// Removes the value assigned to the ID, returning it, or `None` if the ID
+    /// isn't assigned to a value. The last value is moved into its position.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::CowArena;
+    /// let mut arena = CowArena::new();
+    /// let a = arena.insert('A');
+    /// let b = arena.insert('B');
+    ///
+    /// assert_eq!(arena.remove(a), Some('A'));
+    /// assert_eq!(arena.remove(a), None);
+    /// assert!(arena.iter().eq(&['B']));
+    /// assert_eq!(arena.get(b), Some(&'B'));
+    /// ```
+    pub fn remove(&mut self, id: ArenaId<T>) -> Option<T> {
+        let removed = self.current.position(id)?;
+        self.current.slots.get_mut(id.idx).state = State::Free {
+            next_free: self.first_free.replace(id.idx),
+        };
+
+        let last = self.current.values.len - 1;
+        if removed < last {
+            let last_slot = self.current.slots.get(last).value_slot;
+            self.current.slots.get_mut(removed).value_slot = last_slot;
+            match &mut self.current.slots.get_mut(last_slot).state {
+                State::Used { value, .. } => *value = removed,
+                _ => unreachable!(),
+            }
+        }
+        Some(self.current.values.swap_remove(removed))
+    }
+}
+
+impl<T> CowSnapshot<T> {
+    /// Returns the number of values in the arena.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::CowArena;
+    /// let mut arena = CowArena::new();
+    /// arena.insert('A');
+    ///
+    /// assert_eq!(arena.snapshot().len(), 1);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len
+    }
+
+    /// Returns `true` if the arena contains no values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::CowArena;
+    /// let arena = CowArena::<char>::new();
+    /// assert!(arena.snapshot().is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.len == 0
+    }
+
+    /// Returns a reference to the value assigned to the ID, or `None` if it
+    /// isn't assigned to one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::CowArena;
+    /// let mut arena = CowArena::new();
+    /// let a = arena.insert('A');
+    /// let snapshot = arena.snapshot();
+    /// arena.remove(a);
+    ///
+    /// assert_eq!(snapshot.get(a), Some(&'A'));
+    /// assert_eq!(arena.get(a), None);
+    /// ```
+    pub fn get(&self, id: ArenaId<T>) -> Option<&T> {
+        let pos = self.position(id)?;
+        Some(self.values.get(pos))
+    }
+
+    /// Returns `true` if the ID is assigned to a value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::CowArena;
+    /// let mut arena = CowArena::new();
+    /// let a = arena.insert('A');
+    /// assert!(arena.contains(a));
+    ///
+    /// arena.remove(a);
+    /// assert!(!arena.contains(a));
+    /// ```
+    #[inline]
+    pub fn contains(&self, id: ArenaId<T>) -> bool {
+        self.position(id).is_some()
+    }
+
+    /// Returns the ID of the value at the position, or `None` if it is out of
+    /// bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::CowArena;
+    /// let mut arena = CowArena::new();
+    /// let a = arena.insert('A');
+    ///
+    /// assert_eq!(arena.id_at(0), Some(a));
+    /// assert_eq!(arena.id_at(1), None);
+    /// ```
+    pub fn id_at(&self, index: usize) -> Option<ArenaId<T>> {
+        if index >= self.values.len {
+            return None;
+        }
+        let idx = self.slots.get(index).value_slot;
+        match self.slots.get(idx).state {
+            State::Used { uid, .. } => Some(self.id(idx, uid)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns an iterator over the values, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::CowArena;
+    /// let mut arena = CowArena::new();
+    /// arena.insert('A');
+    /// arena.insert('B');
+    ///
+    /// assert!(arena.snapshot().iter().eq(&['A', 'B']));
+    /// ```
+    pub fn iter(&self) -> CowIter<'_, T> {
+        CowIter {
+            chunks: self.values.chunks.iter(),
+            chunk: [].iter(),
+            remaining: self.values.len,
+        }
+    }
+
+    // the position of the value assigned to the ID, if there is one
+    fn position(&self, id: ArenaId<T>) -> Option<usize> {
+        #[cfg(feature = "uuid")]
+        if id.uuid != self.uuid {
+            return None;
+        }
+        if id.idx >= self.slots.len {
+            return None;
+        }
+        match self.slots.get(id.idx).state {
+            State::Used { uid, value } if uid == id.uid => Some(value),
+            _ => None,
+        }
+    }
+
+    fn id(&self, idx: usize, uid: u64) -> ArenaId<T> {
+        ArenaId {
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+            uid,
+            idx,
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for CowArena<T> {
+    type Target = CowSnapshot<T>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.current
+    }
+}
+
+impl<T> Default for CowArena<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Index<ArenaId<T>> for CowSnapshot<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, id: ArenaId<T>) -> &Self::Output {
+        self.get(id).unwrap()
+    }
+}
+
+impl<T> Index<ArenaId<T>> for CowArena<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, id: ArenaId<T>) -> &Self::Output {
+        self.get(id).unwrap()
+    }
+}
+
+impl<T: Clone> std::ops::IndexMut<ArenaId<T>> for CowArena<T> {
+    #[inline]
+    fn index_mut(&mut self, id: ArenaId<T>) -> &mut Self::Output {
+        self.get_mut(id).unwrap()
+    }
+}
+
+impl<T> Clone for CowSnapshot<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            values: self.values.clone(),
+            slots: self.slots.clone(),
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for CowSnapshot<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for CowArena<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.current.fmt(f)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a CowSnapshot<T> {
+    type Item = &'a T;
+    type IntoIter = CowIter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the values of a [`CowArena`] or [`CowSnapshot`].
+///
+/// This struct is created by the [`iter`](CowSnapshot::iter) method on
+/// [`CowSnapshot`].
+pub struct CowIter<'a, T> {
+    chunks: std::slice::Iter<'a, Arc<Vec<T>>>,
+    chunk: std::slice::Iter<'a, T>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for CowIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(val) = self.chunk.next() {
+                self.remaining -= 1;
+                return Some(val);
+            }
+            self.chunk = self.chunks.next()?.iter();
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for CowIter<'a, T> {}
+
+impl<'a, T> FusedIterator for CowIter<'a, T> {}
+
+impl<'a, T> Clone for CowIter<'a, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            chunks: self.chunks.clone(),
+            chunk: self.chunk.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+// A vector split into shared chunks of `CHUNK_LEN` items. Cloning it only
+// clones the pointer to the list of chunks, and modifying it copies the list
+// and the chunk being modified if they are shared.
+struct Chunks<T> {
+    chunks: Arc<Vec<Arc<Vec<T>>>>,
+    len: usize,
+}
+
+impl<T> Chunks<T> {
+    fn new() -> Self {
+        Self {
+            chunks: Arc::new(Vec::new()),
+            len: 0,
+        }
+    }
+
+    #[inline]
+    fn get(&self, i: usize) -> &T {
+        &self.chunks[i / CHUNK_LEN][i % CHUNK_LEN]
+    }
+}
+
+impl<T: Clone> Chunks<T> {
+    fn get_mut(&mut self, i: usize) -> &mut T {
+        let chunks = Arc::make_mut(&mut self.chunks);
+        &mut Arc::make_mut(&mut chunks[i / CHUNK_LEN])[i % CHUNK_LEN]
+    }
+
+    fn push(&mut self, item: T) {
+        let chunks = Arc::make_mut(&mut self.chunks);
+        if self.len % CHUNK_LEN == 0 {
+            chunks.push(Arc::new(Vec::with_capacity(CHUNK_LEN)));
+        }
+        Arc::make_mut(chunks.last_mut().unwrap()).push(item);
+        self.len += 1;
+    }
+
+    // removes the item, replacing it with the last one
+    fn swap_remove(&mut self, i: usize) -> T {
+        let chunks = Arc::make_mut(&mut self.chunks);
+        let mut last_chunk = chunks.pop().unwrap();
+        let last = Arc::make_mut(&mut last_chunk).pop().unwrap();
+        if !last_chunk.is_empty() {
+            chunks.push(last_chunk);
+        }
+        self.len -= 1;
+        if i < self.len {
+            std::mem::replace(self.get_mut(i), last)
+        } else {
+            last
+        }
+    }
+}
+
+impl<T> Clone for Chunks<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            chunks: Arc::clone(&self.chunks),
+            len: self.len,
+        }
+    }
+}
+
+#[test]
+fn writes_only_copy_touched_chunks() {
+    fn shared<T>(a: &Chunks<T>, b: &Chunks<T>) -> Vec<bool> {
+        let pairs = a.chunks.iter().zip(b.chunks.iter());
+        pairs.map(|(a, b)| Arc::ptr_eq(a, b)).collect()
+    }
+
+    let mut arena = CowArena::new();
+    let ids: Vec<_> = (0..CHUNK_LEN * 4).map(|i| arena.insert(i)).collect();
+
+    let snapshot = arena.snapshot();
+    *arena.get_mut(ids[CHUNK_LEN + 1]).unwrap() = 0;
+    assert_eq!(
+        shared(&arena.values, &snapshot.values),
+        [true, false, true, true]
+    );
+    assert_eq!(shared(&arena.slots, &snapshot.slots), [true; 4]);
+
+    let snapshot = arena.snapshot();
+    arena.insert(0);
+    assert_eq!(shared(&arena.values, &snapshot.values), [true; 4]);
+    assert_eq!(shared(&arena.slots, &snapshot.slots), [true; 4]);
+
+    // the last value moves into the removed one's position in the first chunk,
+    // and its slot is in the last chunk
+    let snapshot = arena.snapshot();
+    arena.remove(ids[1]);
+    let touched = [false, true, true, true, false];
+    assert_eq!(shared(&arena.values, &snapshot.values), touched[..4]);
+    assert_eq!(shared(&arena.slots, &snapshot.slots), touched);
+}
+
+#[cfg(not(feature = "uuid"))]
+#[test]
+fn assigns_the_same_ids_as_arena() {
+    let mut cow = CowArena::new();
+    let mut arena = crate::Arena::new();
+    let ids: Vec<_> = (0..4).map(|i| (cow.insert(i), arena.insert(i))).collect();
+    cow.remove(ids[1].0);
+    arena.remove(ids[1].1);
+    assert_eq!(cow.insert(9), arena.insert(9));
+    for (cow_id, arena_id) in ids {
+        assert_eq!(cow_id, arena_id);
+    }
+}
@@ -0,0 +1,242 @@
+//! A cursor for editing an arena while walking over its values.
+
+use crate::{Arena, ArenaId};
+
+impl<T> Arena<T> {
+    /// Returns a cursor pointing at the first value in the arena, which can
+    /// walk over the values while removing, inserting and swapping them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from([1, 2, 3, 4, 5]);
+    /// let mut cursor = arena.cursor_mut();
+    ///
+    /// while let Some((_, val)) = cursor.current() {
+    ///     if *val % 2 == 0 {
+    ///         cursor.remove_current();
+    ///     } else {
+    ///         *val *= 10;
+    ///         cursor.move_next();
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(arena.as_slice(), &[10, 50, 30]);
+    /// ```
+    #[inline]
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            arena: self,
+            index: 0,
+        }
+    }
+}
+
+/// A cursor over the values of an arena, which points at a position in the
+/// arena and can edit the arena around it.
+///
+/// The cursor points either at a value or past the last value, where it has no
+/// [current](CursorMut::current) value.
+///
+/// # Visiting order
+///
+/// The cursor walks the values in the order of [`as_slice`](Arena::as_slice).
+/// Removing a value moves the last value into its position, so the rules below
+/// make sure a walk still visits every value exactly once, as long as it only
+/// swaps with values it has already visited:
+///
+/// - [`remove_current`](CursorMut::remove_current) leaves the cursor at the
+///   same position, which then holds the value that was last, or is past the
+///   end if the removed value was last. Walking forward, that value hasn't
+///   been visited yet, so the cursor should not move after removing. Walking
+///   backward, it has already been visited, so the cursor should move back.
+/// - [`insert`](CursorMut::insert) adds the value at the end, where walking
+///   forward reaches it later.
+/// - [`swap_with`](CursorMut::swap_with) leaves the cursor at the same
+///   position, which then holds the other value. If the walk hasn't reached
+///   the other value yet, the current value moves to where it will be visited
+///   again, and the other value is skipped.
+///
+/// This is created by the [`cursor_mut`](Arena::cursor_mut) method on [`Arena`].
+#[derive(Debug)]
+pub struct CursorMut<'a, T> {
+    arena: &'a mut Arena<T>,
+    index: usize,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    /// Returns the position the cursor points at, which is the arena's length
+    /// if it is past the last value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from(['A', 'B']);
+    /// let mut cursor = arena.cursor_mut();
+    /// assert_eq!(cursor.index(), 0);
+    ///
+    /// cursor.move_next();
+    /// cursor.move_next();
+    /// assert_eq!(cursor.index(), 2);
+    /// ```
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the ID and a mutable reference to the value the cursor points
+    /// at, or `None` if it is past the last value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    ///
+    /// let mut cursor = arena.cursor_mut();
+    /// let (id, val) = cursor.current().unwrap();
+    /// assert_eq!(id, a);
+    /// *val = 'Z';
+    ///
+    /// cursor.move_next();
+    /// assert_eq!(cursor.current(), None);
+    /// assert_eq!(arena[a], 'Z');
+    /// ```
+    pub fn current(&mut self) -> Option<(ArenaId<T>, &mut T)> {
+        let id = self.arena.id_at(self.index)?;
+        self.arena.get_mut(id).map(|val| (id, val))
+    }
+
+    /// Moves the cursor to the next value, returning `true` if there is one.
+    /// Otherwise, the cursor is left past the last value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from(['A', 'B']);
+    /// let mut cursor = arena.cursor_mut();
+    ///
+    /// assert!(cursor.move_next());
+    /// assert_eq!(cursor.current().map(|(_, v)| *v), Some('B'));
+    /// assert!(!cursor.move_next());
+    /// assert!(!cursor.move_next());
+    /// assert_eq!(cursor.current(), None);
+    /// ```
+    pub fn move_next(&mut self) -> bool {
+        if self.index < self.arena.len() {
+            self.index += 1;
+        }
+        self.index < self.arena.len()
+    }
+
+    /// Moves the cursor to the previous value, returning `true` if there is
+    /// one. Otherwise, the cursor is left where it is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from(['A', 'B']);
+    /// let mut cursor = arena.cursor_mut();
+    /// while cursor.move_next() {}
+    ///
+    /// // walk backward from past the end
+    /// let mut seen = Vec::new();
+    /// while cursor.move_prev() {
+    ///     seen.push(*cursor.current().unwrap().1);
+    /// }
+    /// assert_eq!(seen, ['B', 'A']);
+    /// ```
+    pub fn move_prev(&mut self) -> bool {
+        let moved = self.index > 0;
+        if moved {
+            self.index -= 1;
+        }
+        moved
+    }
+
+    /// Removes the value the cursor points at, returning it, or `None` if the
+    /// cursor is past the last value.
+    ///
+    /// The last value is moved into the removed value's position, so the
+    /// cursor points at that value afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    /// let b = arena.insert('B');
+    /// let c = arena.insert('C');
+    ///
+    /// let mut cursor = arena.cursor_mut();
+    /// assert_eq!(cursor.remove_current(), Some('A'));
+    /// assert_eq!(cursor.current().map(|(id, _)| id), Some(c));
+    ///
+    /// assert_eq!(arena.as_slice(), &['C', 'B']);
+    /// assert!(!arena.contains(a));
+    /// ```
+    pub fn remove_current(&mut self) -> Option<T> {
+        let id = self.arena.id_at(self.index)?;
+        self.arena.remove(id)
+    }
+
+    /// Inserts a value at the end of the arena, returning its ID. The cursor
+    /// doesn't move, so walking forward reaches the new value later, unless
+    /// the cursor was past the last value, in which case it now points at the
+    /// new value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from([1, 2]);
+    /// let mut cursor = arena.cursor_mut();
+    ///
+    /// // every value gets a neighbor ten times larger, visited in turn
+    /// while let Some((_, val)) = cursor.current() {
+    ///     if *val < 10 {
+    ///         let neighbor = *val * 10;
+    ///         cursor.insert(neighbor);
+    ///     }
+    ///     cursor.move_next();
+    /// }
+    ///
+    /// assert_eq!(arena.as_slice(), &[1, 2, 10, 20]);
+    /// ```
+    pub fn insert(&mut self, value: T) -> ArenaId<T> {
+        self.arena.insert(value)
+    }
+
+    /// Swaps the positions of the value the cursor points at and the value
+    /// assigned to the ID, without invalidating their IDs, so that the cursor
+    /// then points at the other value. Returns `false`, and does nothing, if
+    /// the cursor is past the last value or the ID isn't assigned to a value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    /// let b = arena.insert('B');
+    ///
+    /// let mut cursor = arena.cursor_mut();
+    /// assert!(cursor.swap_with(b));
+    /// assert_eq!(cursor.current().map(|(id, _)| id), Some(b));
+    ///
+    /// assert_eq!(arena.as_slice(), &['B', 'A']);
+    /// assert_eq!(arena[a], 'A');
+    /// ```
+    pub fn swap_with(&mut self, id: ArenaId<T>) -> bool {
+        match self.arena.id_at(self.index) {
+            Some(current) => self.arena.swap_positions(current, id),
+            None => false,
+        }
+    }
+}
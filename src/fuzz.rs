@@ -0,0 +1,113 @@
+//! Generation of random arenas for fuzzing and property testing.
+//!
+//! Arenas are never generated by filling in their fields directly. Instead, a
+//! random sequence of operations is replayed on an empty arena, so that every
+//! generated arena is one that could have been reached through the public API,
+//! including free slots, reused slots, and IDs that have gone stale.
+
+use crate::{Arena, ArenaId};
+use std::cmp::Ordering;
+
+/// An operation replayed on an arena to generate it.
+#[derive(Debug, Clone)]
+pub(crate) enum Op<T> {
+    /// Inserts the value.
+    Insert(T),
+    /// Removes the ID picked from all IDs issued so far, which may be stale.
+    Remove(usize),
+    /// Swaps the values at the two picked positions.
+    Swap(usize, usize),
+    /// Sorts the values with a random comparator seeded with the value.
+    Sort(u64),
+}
+
+/// Replays the operations on a new arena, returning it along with every ID
+/// that was issued along the way, both live and stale.
+pub(crate) fn replay<T, I>(ops: I) -> (Arena<T>, Vec<ArenaId<T>>)
+where
+    I: IntoIterator<Item = Op<T>>,
+{
+    let mut arena = Arena::new();
+    let mut ids = Vec::new();
+    for op in ops {
+        match op {
+            Op::Insert(val) => ids.push(arena.insert(val)),
+            Op::Remove(i) => {
+                if !ids.is_empty() {
+                    arena.remove(ids[i % ids.len()]);
+                }
+            }
+            Op::Swap(i, j) => {
+                if !arena.is_empty() {
+                    arena.swap(i % arena.len(), j % arena.len());
+                }
+            }
+            Op::Sort(seed) => {
+                let mut rng = SplitMix64(seed);
+                arena.sort_by(|_, _| match rng.next() % 3 {
+                    0 => Ordering::Less,
+                    1 => Ordering::Equal,
+                    _ => Ordering::Greater,
+                });
+            }
+        }
+    }
+    (arena, ids)
+}
+
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+mod arb {
+    use super::{replay, Op};
+    use crate::Arena;
+    use arbitrary::{Arbitrary, Result, Unstructured};
+
+    impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for Op<T> {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            // favor inserts so that arenas tend to grow
+            Ok(match u.int_in_range(0..=5u8)? {
+                0..=2 => Op::Insert(T::arbitrary(u)?),
+                3 => Op::Remove(u.arbitrary()?),
+                4 => Op::Swap(u.arbitrary()?, u.arbitrary()?),
+                _ => Op::Sort(u.arbitrary()?),
+            })
+        }
+    }
+
+    /// Generates an arena by replaying a random sequence of `insert`, `remove`,
+    /// `swap` and `sort_by` calls, so it can contain free and reused slots.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// use arbitrary::{Arbitrary, Unstructured};
+    ///
+    /// let bytes: Vec<u8> = (0..=255).collect();
+    /// let arena = Arena::<u16>::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+    ///
+    /// assert!(arena.len() <= arena.slot_count());
+    /// ```
+    impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for Arena<T> {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            let ops: Vec<Op<T>> = u.arbitrary()?;
+            Ok(replay(ops).0)
+        }
+
+        fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+            let ops: Vec<Op<T>> = Arbitrary::arbitrary_take_rest(u)?;
+            Ok(replay(ops).0)
+        }
+    }
+}
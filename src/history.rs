@@ -0,0 +1,370 @@
+//! An arena with multi-level undo and redo.
+
+use crate::transaction::{Counters, Edit};
+use crate::{Arena, Transaction};
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+
+/// An arena that keeps a history of the changes made to it, so they can be
+/// undone and redone.
+///
+/// Changes are made in named steps with [`edit`](HistoryArena::edit), each of
+/// which is undone and redone as a whole. Undoing a step restores the arena to
+/// exactly the state it was in before, so removed values get their original IDs
+/// back, and redoing it restores the IDs of the values it inserted.
+///
+/// Like [transactions](Arena::begin_transaction), the history stores a clone
+/// of every value that is removed or mutably accessed. To bound its memory,
+/// only the most recent [`max_steps`](HistoryArena::max_steps) steps are kept.
+///
+/// The arena can be read through the history directly, since it implements
+/// [`Deref<Target = Arena<T>>`](Deref).
+///
+/// # Examples
+///
+/// ```
+/// # use arena::HistoryArena;
+/// let mut history = HistoryArena::new();
+/// let a = history.edit("add A", |arena| arena.insert('A'));
+/// history.edit("remove A", |arena| arena.remove(a));
+/// assert!(history.is_empty());
+///
+/// assert_eq!(history.undo(), Some("remove A"));
+/// assert_eq!(history.get(a), Some(&'A'));
+///
+/// assert_eq!(history.redo(), Some("remove A"));
+/// assert_eq!(history.get(a), None);
+/// ```
+pub struct HistoryArena<T> {
+    arena: Arena<T>,
+    undo: VecDeque<Step<T>>,
+    redo: Vec<Step<T>>,
+    max_steps: usize,
+}
+
+// The edits made by a step, and the counters to restore when reverting them.
+struct Step<T> {
+    name: String,
+    edits: Vec<Edit<T>>,
+    counters: Counters,
+}
+
+impl<T> HistoryArena<T> {
+    /// The number of steps kept by [`new`](HistoryArena::new).
+    pub const DEFAULT_MAX_STEPS: usize = 100;
+
+    /// Creates a history over an empty arena, keeping up to
+    /// [`DEFAULT_MAX_STEPS`](HistoryArena::DEFAULT_MAX_STEPS) steps.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::HistoryArena;
+    /// let history = HistoryArena::<char>::new();
+    /// assert_eq!(history.max_steps(), HistoryArena::<char>::DEFAULT_MAX_STEPS);
+    /// ```
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_max_steps(Self::DEFAULT_MAX_STEPS)
+    }
+
+    /// Creates a history over an empty arena, keeping up to `max_steps` steps.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::HistoryArena;
+    /// let history = HistoryArena::<char>::with_max_steps(10);
+    /// assert_eq!(history.max_steps(), 10);
+    /// ```
+    #[inline]
+    pub fn with_max_steps(max_steps: usize) -> Self {
+        Self::from_arena(Arena::new(), max_steps)
+    }
+
+    /// Creates a history over an existing arena, keeping up to `max_steps` steps.
+    /// The arena's current state is the furthest back it can be undone to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::{Arena, HistoryArena};
+    /// let mut history = HistoryArena::from_arena(Arena::from(['A']), 10);
+    /// history.edit("clear", |arena| arena.clear());
+    ///
+    /// history.undo();
+    /// assert_eq!(history.as_slice(), &['A']);
+    /// assert_eq!(history.undo(), None);
+    /// ```
+    pub fn from_arena(arena: Arena<T>, max_steps: usize) -> Self {
+        Self {
+            arena,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            max_steps,
+        }
+    }
+
+    /// Returns the arena, discarding the history.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::HistoryArena;
+    /// let mut history = HistoryArena::new();
+    /// history.edit("add A", |arena| arena.insert('A'));
+    ///
+    /// let arena = history.into_arena();
+    /// assert_eq!(arena.as_slice(), &['A']);
+    /// ```
+    #[inline]
+    pub fn into_arena(self) -> Arena<T> {
+        self.arena
+    }
+
+    /// Returns the maximum number of steps that can be undone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::HistoryArena;
+    /// let history = HistoryArena::<char>::with_max_steps(3);
+    /// assert_eq!(history.max_steps(), 3);
+    /// ```
+    #[inline]
+    pub fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    /// Sets the maximum number of steps that can be undone, discarding the
+    /// oldest steps if there are more than that.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::HistoryArena;
+    /// let mut history = HistoryArena::new();
+    /// history.edit("add A", |arena| arena.insert('A'));
+    /// history.edit("add B", |arena| arena.insert('B'));
+    ///
+    /// history.set_max_steps(1);
+    /// assert_eq!(history.undo(), Some("add B"));
+    /// assert_eq!(history.undo(), None);
+    /// ```
+    pub fn set_max_steps(&mut self, max_steps: usize) {
+        self.max_steps = max_steps;
+        self.trim();
+    }
+
+    /// Returns the name of the step that [`undo`](HistoryArena::undo) would undo.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::HistoryArena;
+    /// let mut history = HistoryArena::new();
+    /// assert_eq!(history.undo_name(), None);
+    ///
+    /// history.edit("add A", |arena| arena.insert('A'));
+    /// assert_eq!(history.undo_name(), Some("add A"));
+    /// ```
+    #[inline]
+    pub fn undo_name(&self) -> Option<&str> {
+        self.undo.back().map(|step| step.name.as_str())
+    }
+
+    /// Returns the name of the step that [`redo`](HistoryArena::redo) would redo.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::HistoryArena;
+    /// let mut history = HistoryArena::new();
+    /// history.edit("add A", |arena| arena.insert('A'));
+    /// assert_eq!(history.redo_name(), None);
+    ///
+    /// history.undo();
+    /// assert_eq!(history.redo_name(), Some("add A"));
+    /// ```
+    #[inline]
+    pub fn redo_name(&self) -> Option<&str> {
+        self.redo.last().map(|step| step.name.as_str())
+    }
+
+    /// Undoes the most recent step, returning its name, or `None` if there is
+    /// nothing to undo.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::HistoryArena;
+    /// let mut history = HistoryArena::new();
+    /// let a = history.edit("add A", |arena| arena.insert('A'));
+    /// history.edit("change A", |arena| arena[a] = 'Z');
+    ///
+    /// assert_eq!(history.undo(), Some("change A"));
+    /// assert_eq!(history.get(a), Some(&'A'));
+    ///
+    /// assert_eq!(history.undo(), Some("add A"));
+    /// assert!(history.is_empty());
+    /// assert_eq!(history.undo(), None);
+    /// ```
+    pub fn undo(&mut self) -> Option<&str> {
+        let step = self.undo.pop_back()?;
+        let step = self.revert(step);
+        self.redo.push(step);
+        self.redo_name()
+    }
+
+    /// Redoes the most recently undone step, returning its name, or `None` if
+    /// there is nothing to redo. Making a new step discards the steps that
+    /// could be redone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::HistoryArena;
+    /// let mut history = HistoryArena::new();
+    /// let a = history.edit("add A", |arena| arena.insert('A'));
+    /// history.undo();
+    ///
+    /// assert_eq!(history.redo(), Some("add A"));
+    /// assert_eq!(history.get(a), Some(&'A'));
+    /// assert_eq!(history.redo(), None);
+    /// ```
+    pub fn redo(&mut self) -> Option<&str> {
+        let step = self.redo.pop()?;
+        let step = self.revert(step);
+        self.undo.push_back(step);
+        self.undo_name()
+    }
+
+    /// Discards all the steps that could be undone or redone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::HistoryArena;
+    /// let mut history = HistoryArena::new();
+    /// history.edit("add A", |arena| arena.insert('A'));
+    ///
+    /// history.clear_history();
+    /// assert_eq!(history.undo(), None);
+    /// assert_eq!(history.as_slice(), &['A']);
+    /// ```
+    pub fn clear_history(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    // reverts the step, returning the step that will revert it back
+    fn revert(&mut self, step: Step<T>) -> Step<T> {
+        let counters = Counters::of(&self.arena);
+        let edits = self.arena.revert(step.edits.into_iter(), step.counters);
+        Step {
+            name: step.name,
+            edits,
+            counters,
+        }
+    }
+
+    fn trim(&mut self) {
+        let excess = self.undo.len().saturating_sub(self.max_steps);
+        self.undo.drain(..excess);
+    }
+}
+
+impl<T: Clone> HistoryArena<T> {
+    /// Makes changes to the arena as a single named step, returning the result
+    /// of `f`. This discards any steps that could be redone.
+    ///
+    /// `f` is given the step's [`Transaction`], through which it can also
+    /// begin nested transactions. If `f` panics, the changes it made are
+    /// rolled back. Steps that don't change the arena aren't added to the
+    /// history.
+    ///
+    /// If `f` ends the step's transaction by calling
+    /// [`Arena::begin_transaction`] on the arena it dereferences to, the
+    /// changes can't be recorded, so the history is cleared.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::HistoryArena;
+    /// let mut history = HistoryArena::new();
+    /// let (a, b) = history.edit("add A and B", |arena| {
+    ///     (arena.insert('A'), arena.insert('B'))
+    /// });
+    /// history.edit("nothing", |arena| arena.len());
+    ///
+    /// assert_eq!(history.undo(), Some("add A and B"));
+    /// assert!(history.is_empty());
+    ///
+    /// history.redo();
+    /// assert_eq!(history.get(a), Some(&'A'));
+    /// assert_eq!(history.get(b), Some(&'B'));
+    /// ```
+    pub fn edit<R, F>(&mut self, name: impl Into<String>, f: F) -> R
+    where
+        F: FnOnce(&mut Transaction<'_, T>) -> R,
+    {
+        let mut tx = self.arena.begin_transaction();
+        let result = f(&mut tx);
+        let Some((edits, counters)) = tx.into_edits() else {
+            // the earlier steps can't be undone past the unrecorded changes
+            self.clear_history();
+            return result;
+        };
+        if !edits.is_empty() {
+            self.redo.clear();
+            self.undo.push_back(Step {
+                name: name.into(),
+                edits,
+                counters,
+            });
+            self.trim();
+        }
+        result
+    }
+}
+
+impl<T> Deref for HistoryArena<T> {
+    type Target = Arena<T>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.arena
+    }
+}
+
+impl<T> Default for HistoryArena<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<Arena<T>> for HistoryArena<T> {
+    #[inline]
+    fn from(arena: Arena<T>) -> Self {
+        Self::from_arena(arena, Self::DEFAULT_MAX_STEPS)
+    }
+}
+
+impl<T: Debug> Debug for HistoryArena<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HistoryArena")
+            .field("arena", &self.arena)
+            .field(
+                "undo",
+                &self.undo.iter().map(|s| &s.name).collect::<Vec<_>>(),
+            )
+            .field(
+                "redo",
+                &self.redo.iter().map(|s| &s.name).collect::<Vec<_>>(),
+            )
+            .field("max_steps", &self.max_steps)
+            .finish()
+    }
+}
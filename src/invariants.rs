@@ -0,0 +1,174 @@
+use crate::{Arena, State};
+use std::fmt::{Display, Formatter};
+
+impl<T> Arena<T> {
+    /// Checks that the arena's internal slot table is consistent, returning the
+    /// first problem found if it isn't.
+    ///
+    /// This verifies that:
+    ///
+    /// - there is a slot for every value
+    /// - every used slot's value points back to it
+    /// - every uid is below the arena's next uid
+    /// - the count of reserved slots is up to date
+    /// - the free list is acyclic and links up exactly the free slots
+    ///
+    /// An arena can only become inconsistent through a bug, so this is mostly
+    /// useful for tracking such bugs down. With the `debug-checks` feature, it is
+    /// run after every call that modifies the arena, and panics on failure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from(['A', 'B', 'C']);
+    /// arena.remove_at(0);
+    /// arena.sort();
+    ///
+    /// assert_eq!(arena.check_invariants(), Ok(()));
+    /// ```
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        let len = self.values.len();
+        if len > self.slots.len() {
+            return Err(InvariantViolation::MoreValuesThanSlots {
+                values: len,
+                slots: self.slots.len(),
+            });
+        }
+
+        let mut used = 0;
+        let mut free = 0;
+        let mut reserved = 0;
+        for (idx, slot) in self.slots.iter().enumerate() {
+            match slot.state {
+                State::Used { uid, value } => {
+                    if uid >= self.next_uid {
+                        return Err(InvariantViolation::UidNotBelowNextUid { slot: idx, uid });
+                    }
+                    if value >= len {
+                        return Err(InvariantViolation::ValueOutOfBounds { slot: idx, value });
+                    }
+                    if self.slots[value].value_slot != idx {
+                        return Err(InvariantViolation::ValueSlotMismatch { slot: idx, value });
+                    }
+                    used += 1;
+                }
+                State::Free { .. } => free += 1,
+                State::Reserved { uid } => {
+                    if uid >= self.next_uid {
+                        return Err(InvariantViolation::UidNotBelowNextUid { slot: idx, uid });
+                    }
+                    reserved += 1;
+                }
+            }
+        }
+
+        // since every used slot's value points back to it, no two used slots
+        // share a value, so they cover all values if there are enough of them
+        if used != len {
+            return Err(InvariantViolation::ValueWithoutSlot);
+        }
+        if reserved != self.reserved {
+            return Err(InvariantViolation::ReservedCountMismatch {
+                counted: reserved,
+                recorded: self.reserved,
+            });
+        }
+
+        let mut visited = vec![false; self.slots.len()];
+        let mut next = self.first_free;
+        while let Some(idx) = next {
+            match self.slots.get(idx).map(|slot| &slot.state) {
+                Some(State::Free { next_free }) => {
+                    if visited[idx] {
+                        return Err(InvariantViolation::FreeListCycle { slot: idx });
+                    }
+                    visited[idx] = true;
+                    free -= 1;
+                    next = *next_free;
+                }
+                _ => return Err(InvariantViolation::FreeListNotFree { slot: idx }),
+            }
+        }
+
+        if free > 0 {
+            let slot = (0..self.slots.len())
+                .find(|&idx| matches!(self.slots[idx].state, State::Free { .. }) && !visited[idx])
+                .unwrap();
+            return Err(InvariantViolation::FreeSlotNotInFreeList { slot });
+        }
+
+        Ok(())
+    }
+
+    /// Runs [`check_invariants`](Arena::check_invariants) if the `debug-checks`
+    /// feature is enabled, panicking if it fails.
+    #[inline]
+    pub(crate) fn debug_check(&self) {
+        #[cfg(feature = "debug-checks")]
+        if let Err(err) = self.check_invariants() {
+            panic!("arena invariant violated: {err}");
+        }
+    }
+}
+
+/// An inconsistency in an arena's slot table, found by [`Arena::check_invariants`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// The arena has more values than slots to assign them to.
+    MoreValuesThanSlots { values: usize, slots: usize },
+    /// A used slot has a uid that the arena hasn't issued yet.
+    UidNotBelowNextUid { slot: usize, uid: u64 },
+    /// A used slot points to a value past the end of the arena.
+    ValueOutOfBounds { slot: usize, value: usize },
+    /// A used slot points to a value which doesn't point back to it.
+    ValueSlotMismatch { slot: usize, value: usize },
+    /// A value isn't assigned to any used slot.
+    ValueWithoutSlot,
+    /// The number of reserved slots doesn't match the arena's count of them.
+    ReservedCountMismatch { counted: usize, recorded: usize },
+    /// The free list visits the same slot twice.
+    FreeListCycle { slot: usize },
+    /// The free list contains a slot that is used or doesn't exist.
+    FreeListNotFree { slot: usize },
+    /// A free slot can't be reached from the free list.
+    FreeSlotNotInFreeList { slot: usize },
+}
+
+impl Display for InvariantViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MoreValuesThanSlots { values, slots } => {
+                write!(f, "arena has {values} values but only {slots} slots")
+            }
+            Self::UidNotBelowNextUid { slot, uid } => {
+                write!(f, "slot {slot} has uid {uid}, which is not below next_uid")
+            }
+            Self::ValueOutOfBounds { slot, value } => {
+                write!(f, "slot {slot} points to out of bounds value {value}")
+            }
+            Self::ValueSlotMismatch { slot, value } => {
+                write!(
+                    f,
+                    "slot {slot} points to value {value}, which doesn't point back"
+                )
+            }
+            Self::ValueWithoutSlot => f.write_str("a value isn't assigned to any slot"),
+            Self::ReservedCountMismatch { counted, recorded } => {
+                write!(
+                    f,
+                    "arena has {counted} reserved slots but recorded {recorded}"
+                )
+            }
+            Self::FreeListCycle { slot } => write!(f, "free list visits slot {slot} twice"),
+            Self::FreeListNotFree { slot } => {
+                write!(f, "free list contains slot {slot}, which isn't free")
+            }
+            Self::FreeSlotNotInFreeList { slot } => {
+                write!(f, "free slot {slot} is not in the free list")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
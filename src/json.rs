@@ -0,0 +1,146 @@
+//! Human-readable JSON helpers, enabled with the `json` feature.
+//!
+//! Arenas are written as an object holding the arena's `next_uid` and a map
+//! of its values, in order, keyed by their IDs. Each key is the ID's slot index
+//! and uid, separated by a colon:
+//!
+//! ```json
+//! {
+//!   "next_uid": 3,
+//!   "values": {
+//!     "0:0": "A",
+//!     "2:2": "C"
+//!   }
+//! }
+//! ```
+
+use crate::Arena;
+use serde::de::{self, DeserializeOwned, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Formatter;
+use std::marker::PhantomData;
+
+impl<T: Serialize> Arena<T> {
+    /// Serializes the arena to a pretty-printed JSON string, with every value
+    /// keyed by its ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from(['A', 'B', 'C']);
+    /// arena.remove_at(1);
+    ///
+    /// assert_eq!(
+    ///     arena.to_json_pretty().unwrap(),
+    ///     r#"{
+    ///   "next_uid": 3,
+    ///   "values": {
+    ///     "0:0": "A",
+    ///     "2:2": "C"
+    ///   }
+    /// }"#
+    /// );
+    /// ```
+    pub fn to_json_pretty(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&JsonArena {
+            next_uid: self.next_uid,
+            values: JsonValues(self),
+        })
+    }
+}
+
+impl<T: DeserializeOwned> Arena<T> {
+    /// Deserializes an arena from JSON written by [`to_json_pretty`](Arena::to_json_pretty).
+    ///
+    /// The IDs are checked to make sure they describe a valid arena, so any
+    /// malformed or duplicate keys will return an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from(['A', 'B', 'C']);
+    /// let b = arena.id_at(1).unwrap();
+    /// arena.remove(b);
+    ///
+    /// let json = arena.to_json_pretty().unwrap();
+    /// let loaded: Arena<char> = Arena::from_json(&json).unwrap();
+    ///
+    /// assert_eq!(loaded.as_slice(), &['A', 'C']);
+    /// assert_eq!(loaded.get(b), None);
+    ///
+    /// let dupes = r#"{ "next_uid": 3, "values": { "0:0": "A", "0:1": "B" } }"#;
+    /// assert!(Arena::<char>::from_json(dupes).is_err());
+    /// ```
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        let de: DeJsonArena<T> = serde_json::from_str(json)?;
+        Self::from_entries(de.next_uid, de.values.0).map_err(de::Error::custom)
+    }
+}
+
+#[derive(Serialize)]
+struct JsonArena<'a, T> {
+    next_uid: u64,
+    values: JsonValues<'a, T>,
+}
+
+struct JsonValues<'a, T>(&'a Arena<T>);
+
+impl<'a, T: Serialize> Serialize for JsonValues<'a, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (id, val) in self.0.pairs() {
+            map.serialize_entry(&format!("{}:{}", id.idx, id.uid), val)?;
+        }
+        map.end()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(bound = "T: DeserializeOwned")]
+struct DeJsonArena<T> {
+    next_uid: u64,
+    values: DeJsonValues<T>,
+}
+
+// keeps the entries in the order they were written, which is the value order
+struct DeJsonValues<T>(Vec<(usize, u64, T)>);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for DeJsonValues<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(ValuesVisitor(PhantomData))
+    }
+}
+
+struct ValuesVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for ValuesVisitor<T> {
+    type Value = DeJsonValues<T>;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str("a map of values keyed by \"idx:uid\" IDs")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some(key) = map.next_key::<String>()? {
+            let (idx, uid) = key
+                .split_once(':')
+                .and_then(|(idx, uid)| Some((idx.parse().ok()?, uid.parse().ok()?)))
+                .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Str(&key), &self))?;
+            entries.push((idx, uid, map.next_value()?));
+        }
+        Ok(DeJsonValues(entries))
+    }
+}
@@ -92,6 +92,21 @@
 //! assert_eq!(ids.next(), None);
 //! ```
 //!
+//! # Features
+//!
+//! - `uuid`: gives every arena a random UUID which is stored in its IDs, so that
+//!   IDs from one arena are never valid in another.
+//! - `serde`: implements `Serialize` and `Deserialize` for [`Arena`] and [`ArenaId`],
+//!   and adds `ArenaSeed` for loading serialized values into an existing arena.
+//!   A deserialized arena holds its values in the same order as when it was
+//!   serialized.
+//! - `json`: adds `Arena::to_json_pretty` and `Arena::from_json`.
+//! - `bytemuck`: adds byte views of arenas whose values are `Pod`.
+//! - `arbitrary`: implements `arbitrary::Arbitrary` for [`Arena`], for fuzzing.
+//! - `proptest`: adds the `strategy` module of `proptest` strategies for arenas.
+//! - `debug-checks`: runs [`Arena::check_invariants`] after every call that modifies
+//!   an arena, panicking if it fails. This is slow, and only meant for debugging.
+//!
 //! # Performance
 //!
 //! Lookups by ID do a few checks, so they are slower than `Vec<T>` indexing, but like
@@ -104,19 +119,53 @@
 //! should never assume the values or IDs in an arena remain in the order you added them.
 
 use std::cmp::Ordering;
+use std::iter::FusedIterator;
 use std::marker::PhantomData;
-use std::ops::{Deref, Index, IndexMut};
+use std::ops::{Bound, Deref, Index, IndexMut, Range, RangeBounds};
 #[cfg(feature = "uuid")]
 use uuid::Uuid;
 
+mod batch;
+mod changes;
+mod commands;
+mod concurrent;
+mod cow;
+mod cursor;
+mod history;
+mod invariants;
+mod patch;
+mod sharded;
+mod snapshot;
+mod transaction;
+
+pub use batch::{IdRange, InsertedIds, InsertedIdsIter};
+pub use changes::Change;
+use changes::ChangeLog;
+pub use commands::ArenaCommands;
+pub use concurrent::ConcurrentArena;
+pub use cow::{CowArena, CowIter, CowSnapshot};
+pub use cursor::CursorMut;
+pub use history::HistoryArena;
+pub use invariants::InvariantViolation;
+pub use patch::{ArenaPatch, PatchError};
+pub use sharded::{Shard, ShardRef, ShardRefMut, ShardedArena};
+pub use snapshot::{SnapshotError, SnapshotValue, SNAPSHOT_VERSION};
+pub use transaction::Transaction;
+use transaction::UndoLog;
+
 /// A contiguous growable container which assigns and returns IDs to values when they are
 /// added to it.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Arena<T> {
     values: Vec<T>,
     slots: Vec<Slot>,
     next_uid: u64,
     first_free: Option<usize>,
+    // number of slots in the `Reserved` state
+    reserved: usize,
+    changes: ChangeLog<T>,
+    // only present while a transaction is open
+    undo: Option<UndoLog<T>>,
     #[cfg(feature = "uuid")]
     uuid: Uuid,
 }
@@ -138,6 +187,9 @@ impl<T> Arena<T> {
             slots: Vec::new(),
             next_uid: 1,
             first_free: None,
+            reserved: 0,
+            changes: ChangeLog::new(),
+            undo: None,
         }
     }
 
@@ -148,6 +200,9 @@ impl<T> Arena<T> {
             slots: Vec::new(),
             next_uid: 1,
             first_free: None,
+            reserved: 0,
+            changes: ChangeLog::new(),
+            undo: None,
             uuid: Uuid::new_v4(),
         }
     }
@@ -168,6 +223,9 @@ impl<T> Arena<T> {
             slots: Vec::with_capacity(capacity),
             next_uid: 1,
             first_free: None,
+            reserved: 0,
+            changes: ChangeLog::new(),
+            undo: None,
             #[cfg(feature = "uuid")]
             uuid: Uuid::new_v4(),
         }
@@ -230,7 +288,7 @@ impl<T> Arena<T> {
     /// ```
     #[inline]
     pub fn free_slot_count(&self) -> usize {
-        self.slot_count() - self.len()
+        self.slot_count() - self.len() - self.reserved
     }
 
     /// Extracts a slice containing all the arena's values.
@@ -278,6 +336,7 @@ impl<T> Arena<T> {
     /// example, with the `rayon` library).
     #[inline]
     pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self.touch_values(0..self.len());
         self.values.as_mut_slice()
     }
 
@@ -285,6 +344,7 @@ impl<T> Arena<T> {
     /// raw pointer valid for zero sized reads if the arena didn't allocate.
     #[inline]
     pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.touch_values(0..self.len());
         self.values.as_mut_ptr()
     }
 
@@ -356,10 +416,12 @@ impl<T> Arena<T> {
         if !self.match_id(&id) {
             return None;
         }
-        match &self.slots.get(id.idx)?.state {
-            State::Used { uid, value } if *uid == id.uid => Some(&mut self.values[*value]),
-            _ => None,
-        }
+        let pos = match &self.slots.get(id.idx)?.state {
+            State::Used { uid, value } if *uid == id.uid => *value,
+            _ => return None,
+        };
+        self.touch_values(pos..pos + 1);
+        Some(&mut self.values[pos])
     }
 
     /// Returns a pair of mutable references correspding to the pair of
@@ -391,7 +453,14 @@ impl<T> Arena<T> {
         if !self.match_id(&a) || !self.match_id(&b) {
             return (None, None);
         }
-        match (self.index_of(a), self.index_of(b)) {
+        let (pos_a, pos_b) = (self.index_of(a), self.index_of(b));
+        if let Some(pos) = pos_a {
+            self.touch_values(pos..pos + 1);
+        }
+        if let Some(pos) = pos_b {
+            self.touch_values(pos..pos + 1);
+        }
+        match (pos_a, pos_b) {
             (Some(a), Some(b)) => {
                 assert_ne!(a, b);
                 let (lower, upper) = self.values.split_at_mut(a.max(b));
@@ -578,7 +647,30 @@ impl<T> Arena<T> {
         F: FnOnce(ArenaId<T>) -> T,
     {
         let value = self.values.len();
-        let idx = match self.first_free.take() {
+        let idx = self.alloc_slot(State::Used {
+            uid: self.next_uid,
+            value,
+        });
+        self.slot_mut(value).value_slot = idx;
+        let id = ArenaId::<T> {
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+            uid: self.next_uid,
+            idx,
+            _ty: PhantomData,
+        };
+        self.next_uid += 1;
+        let value = create(id);
+        self.values_push(value);
+        self.log_change(|_| Change::Inserted(id));
+        self.debug_check();
+        id
+    }
+
+    // Puts the state in a free slot, or a new one if there are none, returning
+    // the slot's index.
+    fn alloc_slot(&mut self, state: State) -> usize {
+        match self.first_free.take() {
             Some(idx) => {
                 match &self.slots[idx].state {
                     State::Free { next_free } => {
@@ -586,35 +678,158 @@ impl<T> Arena<T> {
                     }
                     _ => unreachable!(),
                 }
-                self.slots[idx].state = State::Used {
-                    uid: self.next_uid,
-                    value,
-                };
+                self.slot_mut(idx).state = state;
                 idx
             }
             None => {
-                let idx = self.slots.len();
-                self.slots.push(Slot {
+                self.slots_push(Slot {
                     value_slot: 0,
-                    state: State::Used {
-                        uid: self.next_uid,
-                        value,
-                    },
+                    state,
                 });
-                idx
+                self.slots.len() - 1
             }
-        };
-        self.slots[value].value_slot = idx;
-        let id = ArenaId::<T> {
+        }
+    }
+
+    // the free slots, in the order they will be allocated
+    pub(crate) fn free_list(&self) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(self.first_free, |&idx| match self.slots[idx].state {
+            State::Free { next_free } => next_free,
+            _ => unreachable!(),
+        })
+    }
+
+    /// Reserves an ID for a value that will be added later with [`fill`](Arena::fill).
+    ///
+    /// Until it is filled, the ID is not assigned to any value, so the arena
+    /// treats it like a removed value's ID. This lets values that refer to each
+    /// other know each other's IDs before any of them are constructed.
+    ///
+    /// Reservations are kept by [snapshots](Arena::write_snapshot), but not by
+    /// the `serde` implementation, which only stores values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::{Arena, ArenaId};
+    /// #[derive(Debug)]
+    /// struct Node {
+    ///     next: ArenaId<Node>,
+    /// }
+    ///
+    /// let mut arena = Arena::new();
+    /// let a = arena.reserve_id();
+    /// let b = arena.reserve_id();
+    ///
+    /// assert!(arena.is_reserved(a));
+    /// assert!(arena.get(a).is_none());
+    /// assert_eq!(arena.len(), 0);
+    ///
+    /// arena.fill(a, Node { next: b }).unwrap();
+    /// arena.fill(b, Node { next: a }).unwrap();
+    ///
+    /// assert_eq!(arena[arena[a].next].next, a);
+    /// ```
+    pub fn reserve_id(&mut self) -> ArenaId<T> {
+        let uid = self.next_uid;
+        let idx = self.alloc_slot(State::Reserved { uid });
+        self.next_uid += 1;
+        self.reserved += 1;
+        self.debug_check();
+        ArenaId::<T> {
             #[cfg(feature = "uuid")]
             uuid: self.uuid,
-            uid: self.next_uid,
+            uid,
             idx,
             _ty: PhantomData,
+        }
+    }
+
+    /// Returns `true` if the ID was reserved with [`reserve_id`](Arena::reserve_id)
+    /// and hasn't been filled or cancelled yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let id = arena.reserve_id();
+    ///
+    /// assert!(arena.is_reserved(id));
+    ///
+    /// arena.fill(id, 'A').unwrap();
+    ///
+    /// assert!(!arena.is_reserved(id));
+    /// ```
+    pub fn is_reserved(&self, id: ArenaId<T>) -> bool {
+        #[cfg(feature = "uuid")]
+        if !self.match_id(&id) {
+            return false;
+        }
+        matches!(
+            self.slots.get(id.idx).map(|slot| &slot.state),
+            Some(State::Reserved { uid }) if *uid == id.uid
+        )
+    }
+
+    /// Adds a value to the arena, assigning it the reserved ID.
+    ///
+    /// If the ID isn't reserved, because it was already filled or cancelled,
+    /// the value is given back as an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let id = arena.reserve_id();
+    ///
+    /// assert_eq!(arena.fill(id, 'A'), Ok(()));
+    /// assert_eq!(arena.fill(id, 'B'), Err('B'));
+    /// assert_eq!(arena.get(id), Some(&'A'));
+    /// ```
+    pub fn fill(&mut self, id: ArenaId<T>, value: T) -> Result<(), T> {
+        if !self.is_reserved(id) {
+            return Err(value);
+        }
+        let pos = self.values.len();
+        self.slot_mut(id.idx).state = State::Used {
+            uid: id.uid,
+            value: pos,
         };
-        self.next_uid += 1;
-        self.values.push(create(id));
-        id
+        self.slot_mut(pos).value_slot = id.idx;
+        self.values_push(value);
+        self.reserved -= 1;
+        self.log_change(|_| Change::Inserted(id));
+        self.debug_check();
+        Ok(())
+    }
+
+    /// Cancels the reservation of an ID, freeing up its slot. Returns `false` if
+    /// the ID isn't reserved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::<char>::new();
+    /// let id = arena.reserve_id();
+    ///
+    /// assert!(arena.cancel_reservation(id));
+    /// assert!(!arena.cancel_reservation(id));
+    /// assert_eq!(arena.fill(id, 'A'), Err('A'));
+    /// assert_eq!(arena.free_slot_count(), 1);
+    /// ```
+    pub fn cancel_reservation(&mut self, id: ArenaId<T>) -> bool {
+        if !self.is_reserved(id) {
+            return false;
+        }
+        self.slot_mut(id.idx).state = State::Free {
+            next_free: self.first_free.replace(id.idx),
+        };
+        self.reserved -= 1;
+        self.debug_check();
+        true
     }
 
     /// Removes the value from the arena assigned to the ID. If the value existed
@@ -632,37 +847,146 @@ impl<T> Arena<T> {
     ///
     /// ```
     pub fn remove(&mut self, id: ArenaId<T>) -> Option<T> {
+        self.remove_with(id, |_, _| {})
+    }
+
+    /// Removes the value from the arena assigned to the ID, like [`remove`](Arena::remove),
+    /// calling `on_swap` with the two positions if values had to be swapped.
+    ///
+    /// Removal swaps the removed value with the last value, then truncates the
+    /// arena, so anything indexed by position, such as a buffer mirroring
+    /// [`as_slice`](Arena::as_slice), can be kept in sync by swapping the same
+    /// two positions and then truncating it to the arena's new length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    /// let b = arena.insert('B');
+    /// let c = arena.insert('C');
+    ///
+    /// let mut mirror = vec!['a', 'b', 'c'];
+    ///
+    /// assert_eq!(arena.remove_with(a, |i, j| mirror.swap(i, j)), Some('A'));
+    /// mirror.truncate(arena.len());
+    ///
+    /// assert_eq!(arena.as_slice(), &['C', 'B']);
+    /// assert_eq!(mirror, ['c', 'b']);
+    /// ```
+    pub fn remove_with<F>(&mut self, id: ArenaId<T>, mut on_swap: F) -> Option<T>
+    where
+        F: FnMut(usize, usize),
+    {
         #[cfg(feature = "uuid")]
         if !self.match_id(&id) {
             return None;
         }
         // get the position of the removed value
-        let removed_val = match &self.slots[id.idx].state {
+        let removed_val = match &self.slots.get(id.idx)?.state {
             State::Used { uid, value } if *uid == id.uid => *value,
             _ => return None,
         };
 
         // free up the slot of the removed value
-        self.slots[id.idx].state = State::Free {
+        self.slot_mut(id.idx).state = State::Free {
             next_free: self.first_free.replace(id.idx),
         };
+        self.log_change(|_| Change::Removed(id));
 
         // check if the removed value is the last in the list
         let last_val = self.values.len() - 1;
         if removed_val < last_val {
             // if not, move the last value into the removed value's slot
             let last_slot = self.slots[last_val].value_slot;
-            self.slots[removed_val].value_slot = last_slot;
-            match &mut self.slots[last_slot].state {
-                State::Used { uid, value } => *value = removed_val,
+            self.slot_mut(removed_val).value_slot = last_slot;
+            match &mut self.slot_mut(last_slot).state {
+                State::Used { value, .. } => *value = removed_val,
                 _ => unreachable!(),
             }
 
             // then also move the value into the removed value's position
-            Some(self.values.swap_remove(removed_val))
+            self.values_swap(removed_val, last_val);
+            let value = self.values_pop().unwrap();
+            on_swap(removed_val, last_val);
+            self.log_change(|arena| Change::Moved {
+                id: arena.slot_ids().id(last_slot),
+                from: last_val,
+                to: removed_val,
+            });
+            self.debug_check();
+            Some(value)
         } else {
-            self.pop()
+            // the slot was already freed above, so don't use `pop()` here
+            let value = self.values_pop();
+            self.debug_check();
+            value
+        }
+    }
+
+    /// Replaces the value assigned to the ID, returning the old value. The ID
+    /// stays valid, and the new value takes the old one's position.
+    ///
+    /// If the ID isn't assigned to a value, the new value is dropped and `None`
+    /// is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    /// let b = arena.insert('B');
+    ///
+    /// assert_eq!(arena.replace(a, 'Z'), Some('A'));
+    /// assert_eq!(arena.as_slice(), &['Z', 'B']);
+    /// assert_eq!(arena.get(a), Some(&'Z'));
+    ///
+    /// arena.remove(b);
+    /// assert_eq!(arena.replace(b, 'Y'), None);
+    /// ```
+    #[inline]
+    pub fn replace(&mut self, id: ArenaId<T>, value: T) -> Option<T> {
+        self.get_mut(id).map(|val| std::mem::replace(val, value))
+    }
+
+    /// Assigns a new ID to the value assigned to this one, returning it. The
+    /// value keeps its position, but the old ID, and any copies of it, become
+    /// stale.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    /// let b = arena.insert('B');
+    ///
+    /// let new_a = arena.reissue(a).unwrap();
+    ///
+    /// assert_ne!(new_a, a);
+    /// assert_eq!(arena.get(a), None);
+    /// assert_eq!(arena.get(new_a), Some(&'A'));
+    /// assert_eq!(arena.as_slice(), &['A', 'B']);
+    ///
+    /// assert_eq!(arena.reissue(a), None);
+    /// ```
+    pub fn reissue(&mut self, id: ArenaId<T>) -> Option<ArenaId<T>> {
+        self.index_of(id)?;
+        let uid = self.next_uid;
+        match &mut self.slot_mut(id.idx).state {
+            State::Used { uid: old, .. } => *old = uid,
+            _ => unreachable!(),
         }
+        self.next_uid += 1;
+        let new_id = ArenaId { uid, ..id };
+        self.log_change(|_| Change::Reissued {
+            old: id,
+            new: new_id,
+        });
+        self.debug_check();
+        Some(new_id)
     }
 
     /// Removes the value at the specified index and returns it.
@@ -684,8 +1008,34 @@ impl<T> Arena<T> {
         self.remove(self.id_at(index)?)
     }
 
+    /// Removes the value at the specified index and returns it, calling `on_swap`
+    /// with the two positions if values had to be swapped, like
+    /// [`remove_with`](Arena::remove_with).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from(['A', 'B', 'C']);
+    /// let mut swaps = Vec::new();
+    ///
+    /// assert_eq!(arena.remove_at_with(0, |i, j| swaps.push((i, j))), Some('A'));
+    /// assert_eq!(arena.remove_at_with(1, |i, j| swaps.push((i, j))), Some('B'));
+    ///
+    /// // removing the last value doesn't need a swap
+    /// assert_eq!(swaps, [(0, 2)]);
+    /// ```
+    pub fn remove_at_with<F>(&mut self, index: usize, on_swap: F) -> Option<T>
+    where
+        F: FnMut(usize, usize),
+    {
+        self.remove_with(self.id_at(index)?, on_swap)
+    }
+
     /// Pops a value off the end of the arena and returns it.
     ///
+    /// This never moves any other values.
+    ///
     /// # Examples
     ///
     /// ```
@@ -699,28 +1049,41 @@ impl<T> Arena<T> {
     /// ```
     #[inline]
     pub fn pop(&mut self) -> Option<T> {
-        let value = self.values.pop()?;
+        let value = self.pop_value()?;
+        self.debug_check();
+        Some(value)
+    }
+
+    // pops the last value and frees its slot, without checking the invariants
+    fn pop_value(&mut self) -> Option<T> {
+        let value = self.values_pop()?;
         let slot = self.slots[self.values.len()].value_slot;
-        self.slots[slot].state = State::Free {
+        self.log_change(|arena| Change::Removed(arena.slot_ids().id(slot)));
+        self.slot_mut(slot).state = State::Free {
             next_free: self.first_free.replace(slot),
         };
         Some(value)
     }
 
     fn clear_opt(&mut self, clear_slots: bool) {
+        for pos in 0..self.values.len() {
+            self.log_change(|arena| Change::Removed(arena.slot_ids().value_id(pos)));
+        }
+        let len = self.values.len();
+        self.values_clear();
         if clear_slots {
-            self.slots.clear();
+            self.slots_clear();
             self.first_free = None;
+            self.reserved = 0;
         } else {
-            for i in 0..self.values.len() {
+            for i in 0..len {
                 let slot = self.slots[i].value_slot;
-                self.slots[slot].state = State::Free {
+                self.slot_mut(slot).state = State::Free {
                     next_free: self.first_free.replace(slot),
                 };
             }
         }
-
-        self.values.clear();
+        self.debug_check();
     }
 
     /// Clears all values from the arena. This will free up all the slots,
@@ -824,60 +1187,71 @@ impl<T> Arena<T> {
     pub fn swap(&mut self, i: usize, j: usize) {
         assert!(i < self.len());
         assert!(j < self.len());
+        self.swap_values(i, j);
+        self.debug_check();
+    }
 
+    // swaps two values and remaps their slots, without checking the bounds
+    // or invariants, so that sorting doesn't check them on every swap
+    fn swap_values(&mut self, i: usize, j: usize) {
         if i == j {
             return;
         }
 
-        self.values.swap(i, j);
+        self.values_swap(i, j);
         let slot_i = self.slots[i].value_slot;
         let slot_j = self.slots[j].value_slot;
-        match &mut self.slots[slot_i] {
-            Slot {
-                value_slot,
-                state: State::Used { value, .. },
-            } => {
-                *value_slot = slot_j;
-                *value = j;
-            }
+        self.slot_mut(i).value_slot = slot_j;
+        self.slot_mut(j).value_slot = slot_i;
+        match &mut self.slot_mut(slot_i).state {
+            State::Used { value, .. } => *value = j,
             _ => unreachable!(),
         };
-        match &mut self.slots[slot_j] {
-            Slot {
-                value_slot,
-                state: State::Used { value, .. },
-            } => {
-                *value_slot = slot_i;
-                *value = i;
-            }
+        match &mut self.slot_mut(slot_j).state {
+            State::Used { value, .. } => *value = i,
             _ => unreachable!(),
         };
+        self.log_change(|arena| Change::Moved {
+            id: arena.slot_ids().id(slot_i),
+            from: i,
+            to: j,
+        });
+        self.log_change(|arena| Change::Moved {
+            id: arena.slot_ids().id(slot_j),
+            from: j,
+            to: i,
+        });
     }
 
-    fn quicksort<F: FnMut(&T, &T) -> Ordering>(
-        &mut self,
-        low: usize,
-        high: usize,
-        compare: &mut F,
-    ) {
+    fn quicksort<F, S>(&mut self, low: usize, high: usize, compare: &mut F, on_swap: &mut S)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+        S: FnMut(usize, usize),
+    {
         if low + 1 >= high.wrapping_add(1) {
             return;
         }
+        let mut swap = |arena: &mut Self, i: usize, j: usize| {
+            if i != j {
+                arena.swap_values(i, j);
+                on_swap(i, j);
+            }
+        };
         let p = {
-            let (mut i, mut j) = (low, low);
-            while i <= high {
-                if compare(&self.values[i], &self.values[high]) == Ordering::Greater {
-                    i += 1;
-                } else {
-                    self.swap(i, j);
-                    i += 1;
+            // the pivot is only moved into place after partitioning, so that a
+            // comparator which isn't a total order can't break the recursion
+            let mut j = low;
+            for i in low..high {
+                if compare(&self.values[i], &self.values[high]) != Ordering::Greater {
+                    swap(self, i, j);
                     j += 1;
                 }
             }
-            j - 1
+            swap(self, j, high);
+            j
         };
-        self.quicksort(low, p.wrapping_sub(1), compare);
-        self.quicksort(p + 1, high, compare);
+        self.quicksort(low, p.wrapping_sub(1), compare, on_swap);
+        self.quicksort(p + 1, high, compare, on_swap);
     }
 
     /// Sorts the values in the arena, using the provided function, without
@@ -905,10 +1279,38 @@ impl<T> Arena<T> {
     /// assert_eq!(arena[c], 'C');
     /// ```
     #[inline]
-    pub fn sort_by<F: FnMut(&T, &T) -> Ordering>(&mut self, mut compare: F) {
+    pub fn sort_by<F: FnMut(&T, &T) -> Ordering>(&mut self, compare: F) {
+        self.sort_by_with(compare, |_, _| {});
+    }
+
+    /// Sorts the values in the arena, using the provided function, like
+    /// [`sort_by`](Arena::sort_by), calling `on_swap` with the two positions
+    /// every time two values are swapped.
+    ///
+    /// Applying the same swaps, in order, to anything indexed by position keeps
+    /// it in sync with the arena.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from([3, 1, 2]);
+    /// let mut mirror = vec!['c', 'a', 'b'];
+    ///
+    /// arena.sort_by_with(|a, b| a.cmp(b), |i, j| mirror.swap(i, j));
+    ///
+    /// assert_eq!(arena.as_slice(), &[1, 2, 3]);
+    /// assert_eq!(mirror, ['a', 'b', 'c']);
+    /// ```
+    pub fn sort_by_with<F, S>(&mut self, mut compare: F, mut on_swap: S)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+        S: FnMut(usize, usize),
+    {
         if self.len() > 1 {
-            self.quicksort(0, self.len() - 1, &mut compare);
+            self.quicksort(0, self.len() - 1, &mut compare, &mut on_swap);
         }
+        self.debug_check();
     }
 
     /// Returns the arena as a simple vector of its values.
@@ -948,7 +1350,7 @@ impl<T> Arena<T> {
     /// ```
     #[inline]
     pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
-        self.values.iter_mut()
+        self.as_mut_slice().iter_mut()
     }
 
     /// Returns an iterator over all ID/value pairs in the arena.
@@ -967,14 +1369,18 @@ impl<T> Arena<T> {
     /// assert_eq!(pairs.next(), Some((b, &'B')));
     /// assert_eq!(pairs.next(), Some((c, &'C')));
     /// assert_eq!(pairs.next(), None);
+    ///
+    /// // newest first
+    /// assert_eq!(arena.pairs().len(), 3);
+    /// assert_eq!(arena.pairs().next_back(), Some((c, &'C')));
+    /// assert_eq!(arena.pairs().rev().nth(1), Some((b, &'B')));
     /// ```
     #[inline]
     pub fn pairs(&self) -> Pairs<'_, T> {
         Pairs {
             iter: self.values.iter().enumerate(),
-            slots: &self.slots,
-            #[cfg(feature = "uuid")]
-            uuid: self.uuid,
+            offset: 0,
+            slots: self.slot_ids(),
         }
     }
 
@@ -1002,18 +1408,39 @@ impl<T> Arena<T> {
     ///         unreachable!()
     ///     }
     /// }
+    ///
+    /// let mut pairs = arena.pairs_mut();
+    /// assert_eq!(pairs.len(), 3);
+    ///
+    /// let (id, val) = pairs.next_back().unwrap();
+    /// assert_eq!(id, c);
+    /// *val = 'Z';
+    ///
+    /// assert_eq!(pairs.nth(1).map(|(id, _)| id), Some(b));
+    /// assert_eq!(pairs.next(), None);
+    /// assert_eq!(arena.as_slice(), &['A', 'B', 'Z']);
     /// ```
     #[inline]
     pub fn pairs_mut(&mut self) -> PairsMut<'_, T> {
+        self.touch_values(0..self.len());
         PairsMut {
             iter: self.values.iter_mut().enumerate(),
-            slots: &self.slots,
-            #[cfg(feature = "uuid")]
-            uuid: self.uuid,
+            offset: 0,
+            slots: SlotIds {
+                slots: &self.slots,
+                #[cfg(feature = "uuid")]
+                uuid: self.uuid,
+                _ty: PhantomData,
+            },
         }
     }
 
-    /// Returns an iterator over all IDs in the arena.
+    /// Returns an iterator over the ID/value pairs of the values in a range of
+    /// positions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds, like indexing a slice would.
     ///
     /// # Examples
     ///
@@ -1024,61 +1451,359 @@ impl<T> Arena<T> {
     /// let b = arena.insert('B');
     /// let c = arena.insert('C');
     ///
-    /// let mut ids = arena.ids();
-    /// assert_eq!(ids.next(), Some(a));
-    /// assert_eq!(ids.next(), Some(b));
-    /// assert_eq!(ids.next(), Some(c));
-    /// assert_eq!(ids.next(), None);
+    /// assert!(arena.pairs_range(1..).eq([(b, &'B'), (c, &'C')]));
+    /// assert!(arena.pairs_range(..1).eq([(a, &'A')]));
+    /// assert_eq!(arena.pairs_range(3..).next(), None);
     /// ```
-    #[inline]
-    pub fn ids(&self) -> Ids<'_, T> {
-        Ids {
-            iter: self.slots[..self.len()].iter().enumerate(),
-            _ty: PhantomData,
-            #[cfg(feature = "uuid")]
-            uuid: self.uuid,
+    pub fn pairs_range<R: RangeBounds<usize>>(&self, range: R) -> Pairs<'_, T> {
+        let (start, bounds) = range_bounds(range);
+        Pairs {
+            iter: self.values[bounds].iter().enumerate(),
+            offset: start,
+            slots: self.slot_ids(),
         }
     }
-}
-
-impl<T: Clone> Arena<T> {
-    /// Adds all values from the slice to the arena.
-    #[inline]
-    pub fn extend_from_slice(&mut self, slice: &[T]) {
-        self.values.reserve(slice.len());
-        self.extend(slice.iter().cloned());
-    }
-}
 
-impl<T: Ord> Arena<T> {
-    /// Sorts the values in the arena, without invalidating their IDs.
+    /// Returns a mutable iterator over the ID/value pairs of the values in a
+    /// range of positions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds, like indexing a slice would.
     ///
     /// # Examples
     ///
     /// ```
     /// # use arena::Arena;
     /// let mut arena = Arena::new();
-    /// let c = arena.insert('C');
-    /// let a = arena.insert('A');
-    /// let b = arena.insert('B');
+    /// let a = arena.insert(1);
+    /// let b = arena.insert(2);
+    /// let c = arena.insert(3);
     ///
-    /// assert_eq!(arena.as_slice(), &['C', 'A', 'B']);
-    /// assert_eq!(arena[a], 'A');
-    /// assert_eq!(arena[b], 'B');
-    /// assert_eq!(arena[c], 'C');
+    /// for (id, val) in arena.pairs_mut_range(1..=2) {
+    ///     assert!(id == b || id == c);
+    ///     *val *= 10;
+    /// }
     ///
-    /// arena.sort();
+    /// assert_eq!(arena.as_slice(), &[1, 20, 30]);
+    /// ```
+    pub fn pairs_mut_range<R: RangeBounds<usize>>(&mut self, range: R) -> PairsMut<'_, T> {
+        let (start, bounds) = range_bounds(range);
+        let end = start + self.values[bounds].len();
+        self.touch_values(start..end);
+        PairsMut {
+            iter: self.values[start..end].iter_mut().enumerate(),
+            offset: start,
+            slots: SlotIds {
+                slots: &self.slots,
+                #[cfg(feature = "uuid")]
+                uuid: self.uuid,
+                _ty: PhantomData,
+            },
+        }
+    }
+
+    /// Returns an iterator over chunks of `chunk_size` values, each of which is
+    /// a mutable iterator over the chunk's ID/value pairs.
     ///
-    /// assert_eq!(arena.as_slice(), &['A', 'B', 'C']);
-    /// assert_eq!(arena[a], 'A');
-    /// assert_eq!(arena[b], 'B');
-    /// assert_eq!(arena[c], 'C');
-    #[inline]
+    /// The chunks don't overlap, so they can be handed out to different
+    /// threads. If the number of values isn't a multiple of `chunk_size`, the
+    /// last chunk is shorter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from([1, 2, 3, 4, 5]);
+    /// let ids: Vec<_> = arena.ids().collect();
+    ///
+    /// let mut chunks = arena.chunks_pairs_mut(2);
+    /// assert_eq!(chunks.len(), 3);
+    ///
+    /// let last = chunks.next_back().unwrap();
+    /// assert!(last.map(|(id, _)| id).eq([ids[4]]));
+    ///
+    /// for chunk in chunks {
+    ///     for (_, val) in chunk {
+    ///         *val *= 10;
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(arena.as_slice(), &[10, 20, 30, 40, 5]);
+    /// ```
+    pub fn chunks_pairs_mut(&mut self, chunk_size: usize) -> ChunksPairsMut<'_, T> {
+        self.touch_values(0..self.len());
+        ChunksPairsMut {
+            iter: self.values.chunks_mut(chunk_size).enumerate(),
+            chunk_size,
+            slots: SlotIds {
+                slots: &self.slots,
+                #[cfg(feature = "uuid")]
+                uuid: self.uuid,
+                _ty: PhantomData,
+            },
+        }
+    }
+
+    /// Consumes the arena, returning an iterator over all ID/value pairs in it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// # use std::collections::HashMap;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert(String::from("A"));
+    /// let b = arena.insert(String::from("B"));
+    ///
+    /// let map: HashMap<_, _> = arena.into_pairs().collect();
+    ///
+    /// assert_eq!(map[&a], "A");
+    /// assert_eq!(map[&b], "B");
+    /// ```
+    #[inline]
+    pub fn into_pairs(self) -> IntoPairs<T> {
+        IntoPairs {
+            iter: self.values.into_iter().enumerate(),
+            slots: self.slots,
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+        }
+    }
+
+    /// Returns an iterator over all IDs in the arena, in the same order as the
+    /// values they are assigned to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    /// let b = arena.insert('B');
+    /// let c = arena.insert('C');
+    ///
+    /// let mut ids = arena.ids();
+    /// assert_eq!(ids.next(), Some(a));
+    /// assert_eq!(ids.next(), Some(b));
+    /// assert_eq!(ids.next(), Some(c));
+    /// assert_eq!(ids.next(), None);
+    ///
+    /// // `C` moves into the hole left by `A`
+    /// arena.remove(a);
+    ///
+    /// assert_eq!(arena.ids().len(), 2);
+    /// assert!(arena.ids().eq([c, b]));
+    /// assert!(arena.ids().rev().eq([b, c]));
+    /// ```
+    #[inline]
+    pub fn ids(&self) -> Ids<'_, T> {
+        Ids {
+            iter: self.slots[..self.len()].iter(),
+            slots: self.slot_ids(),
+        }
+    }
+
+    /// Returns an iterator over the IDs of the values in a range of positions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds, like indexing a slice would.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    /// let b = arena.insert('B');
+    /// let c = arena.insert('C');
+    ///
+    /// assert!(arena.ids_range(1..).eq([b, c]));
+    /// assert!(arena.ids_range(..=1).rev().eq([b, a]));
+    /// ```
+    #[inline]
+    pub fn ids_range<R: RangeBounds<usize>>(&self, range: R) -> Ids<'_, T> {
+        let (_, bounds) = range_bounds(range);
+        Ids {
+            iter: self.slots[..self.len()][bounds].iter(),
+            slots: self.slot_ids(),
+        }
+    }
+
+    /// Returns an iterator over all IDs in the arena, in the order of the slots
+    /// they occupy rather than the order of their values.
+    ///
+    /// Unlike the order of values, the slot order isn't changed when values are
+    /// removed, swapped or sorted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    /// let b = arena.insert('B');
+    /// let c = arena.insert('C');
+    ///
+    /// arena.swap(0, 2);
+    ///
+    /// assert!(arena.ids().eq([c, b, a]));
+    /// assert!(arena.ids_by_slot().eq([a, b, c]));
+    /// ```
+    #[inline]
+    pub fn ids_by_slot(&self) -> IdsBySlot<'_, T> {
+        IdsBySlot {
+            iter: self.slots.iter().enumerate(),
+            remaining: self.len(),
+            slots: self.slot_ids(),
+        }
+    }
+
+    #[inline]
+    fn slot_ids(&self) -> SlotIds<'_, T> {
+        SlotIds {
+            slots: &self.slots,
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+            _ty: PhantomData,
+        }
+    }
+
+    // records that the values at the positions are about to be mutably accessed
+    #[inline]
+    fn touch_values(&mut self, positions: Range<usize>) {
+        self.record_values(positions.clone());
+        self.log_modified(positions);
+    }
+}
+
+impl<T: Clone> Arena<T> {
+    /// Adds all values from the slice to the arena.
+    #[inline]
+    pub fn extend_from_slice(&mut self, slice: &[T]) {
+        self.values.reserve(slice.len());
+        self.extend(slice.iter().cloned());
+    }
+}
+
+impl<T: Ord> Arena<T> {
+    /// Sorts the values in the arena, without invalidating their IDs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let c = arena.insert('C');
+    /// let a = arena.insert('A');
+    /// let b = arena.insert('B');
+    ///
+    /// assert_eq!(arena.as_slice(), &['C', 'A', 'B']);
+    /// assert_eq!(arena[a], 'A');
+    /// assert_eq!(arena[b], 'B');
+    /// assert_eq!(arena[c], 'C');
+    ///
+    /// arena.sort();
+    ///
+    /// assert_eq!(arena.as_slice(), &['A', 'B', 'C']);
+    /// assert_eq!(arena[a], 'A');
+    /// assert_eq!(arena[b], 'B');
+    /// assert_eq!(arena[c], 'C');
+    #[inline]
     pub fn sort(&mut self) {
         self.sort_by(|a, b| a.cmp(b));
     }
 }
 
+#[cfg(feature = "bytemuck")]
+impl<T: bytemuck::Pod> Arena<T> {
+    /// Constructs a new arena from the values in a byte slice, assigning IDs to
+    /// them the same way [`From<Vec<T>>`](Arena::from) does.
+    ///
+    /// The bytes don't need to be aligned for `T`, since they are copied into the
+    /// arena. An error is returned if the slice's length isn't a multiple of the
+    /// size of `T`, or if `T` is zero-sized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let bytes = [1u16, 2, 3].map(u16::to_ne_bytes).concat();
+    /// let arena: Arena<u16> = Arena::from_bytes(&bytes).unwrap();
+    ///
+    /// assert_eq!(arena.as_slice(), &[1, 2, 3]);
+    /// assert!(Arena::<u16>::from_bytes(&bytes[1..]).is_err());
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, bytemuck::PodCastError> {
+        let size = std::mem::size_of::<T>();
+        if size == 0 {
+            return Err(bytemuck::PodCastError::SizeMismatch);
+        }
+        if bytes.len() % size != 0 {
+            return Err(bytemuck::PodCastError::OutputSliceWouldHaveSlop);
+        }
+        let mut values = vec![T::zeroed(); bytes.len() / size];
+        bytemuck::cast_slice_mut(values.as_mut_slice()).copy_from_slice(bytes);
+        Ok(Self::from(values))
+    }
+
+    /// Returns the arena's values as a slice of bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let arena: Arena<u32> = Arena::from([1, 2]);
+    ///
+    /// assert_eq!(arena.as_bytes(), [1u32.to_ne_bytes(), 2u32.to_ne_bytes()].concat());
+    /// ```
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(self.values.as_slice())
+    }
+
+    /// Returns the arena's values as a mutable slice of bytes.
+    ///
+    /// Like [`as_mut_slice`](Arena::as_mut_slice), this allows modifying the
+    /// values in place without affecting their IDs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena: Arena<u32> = Arena::from([1, 2]);
+    ///
+    /// arena.as_bytes_mut().copy_from_slice(&[3u32.to_ne_bytes(), 4u32.to_ne_bytes()].concat());
+    /// assert_eq!(arena.as_slice(), &[3, 4]);
+    /// ```
+    #[inline]
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        bytemuck::cast_slice_mut(self.as_mut_slice())
+    }
+}
+
+// the clone isn't part of any open transaction, so it doesn't get the undo log
+impl<T: Clone> Clone for Arena<T> {
+    fn clone(&self) -> Self {
+        Self {
+            values: self.values.clone(),
+            slots: self.slots.clone(),
+            next_uid: self.next_uid,
+            first_free: self.first_free,
+            reserved: self.reserved,
+            changes: self.changes.clone(),
+            undo: None,
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+        }
+    }
+}
+
 impl<T> Default for Arena<T> {
     #[inline]
     fn default() -> Self {
@@ -1134,7 +1859,7 @@ impl<T> From<Vec<T>> for Arena<T> {
         for i in 0..values.len() {
             slots.push(Slot {
                 value_slot: i,
-                state: State::Used { uid: uid, value: i },
+                state: State::Used { uid, value: i },
             });
             uid += 1;
         }
@@ -1143,6 +1868,9 @@ impl<T> From<Vec<T>> for Arena<T> {
             slots,
             first_free: None,
             next_uid: uid,
+            reserved: 0,
+            changes: ChangeLog::new(),
+            undo: None,
             #[cfg(feature = "uuid")]
             uuid: Uuid::new_v4(),
         }
@@ -1180,11 +1908,31 @@ impl<T> IntoIterator for Arena<T> {
     }
 }
 
+impl<'a, T> IntoIterator for &'a Arena<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut Arena<T> {
+    type Item = &'a mut T;
+    type IntoIter = std::slice::IterMut<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
 impl<T> FromIterator<T> for Arena<T> {
     #[inline]
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
         let mut arena = Arena::new();
-        arena.extend(iter.into_iter());
+        arena.extend(iter);
         arena
     }
 }
@@ -1199,6 +1947,7 @@ struct Slot {
 enum State {
     Used { uid: u64, value: usize },
     Free { next_free: Option<usize> },
+    Reserved { uid: u64 },
 }
 
 /// An ID assigned to a value when it was added to an arena.
@@ -1222,18 +1971,22 @@ pub struct ArenaId<T> {
 impl<T> Clone for ArenaId<T> {
     #[inline]
     fn clone(&self) -> Self {
-        Self {
-            #[cfg(feature = "uuid")]
-            uuid: Uuid::new_v4(),
-            uid: self.uid,
-            idx: self.idx,
-            _ty: PhantomData,
-        }
+        *self
     }
 }
 
 impl<T> Copy for ArenaId<T> {}
 
+impl<T> ArenaId<T> {
+    // the index of the ID's slot, for tests that check the slot order without
+    // going through the arena
+    #[doc(hidden)]
+    #[inline]
+    pub fn slot_index(&self) -> usize {
+        self.idx
+    }
+}
+
 impl<T> PartialEq for ArenaId<T> {
     #[cfg(feature = "uuid")]
     #[inline]
@@ -1285,9 +2038,9 @@ impl<T> Ord for ArenaId<T> {
 /// This struct is created by the [`pairs`](Arena::pairs) method on [`Arena`].
 pub struct Pairs<'a, T> {
     iter: std::iter::Enumerate<std::slice::Iter<'a, T>>,
-    slots: &'a [Slot],
-    #[cfg(feature = "uuid")]
-    uuid: Uuid,
+    // position of the first value in the iterated slice
+    offset: usize,
+    slots: SlotIds<'a, T>,
 }
 
 impl<'a, T> Iterator for Pairs<'a, T> {
@@ -1295,32 +2048,48 @@ impl<'a, T> Iterator for Pairs<'a, T> {
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        let (idx, val) = self.iter.next()?;
-        let idx = self.slots[idx].value_slot;
-        match &self.slots[idx].state {
-            State::Used { uid, .. } => Some((
-                ArenaId::<T> {
-                    #[cfg(feature = "uuid")]
-                    uuid: self.uuid,
-                    uid: *uid,
-                    idx,
-                    _ty: PhantomData,
-                },
-                val,
-            )),
-            _ => unreachable!(),
-        }
+        let (pos, val) = self.iter.next()?;
+        Some((self.slots.value_id(self.offset + pos), val))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let (pos, val) = self.iter.nth(n)?;
+        Some((self.slots.value_id(self.offset + pos), val))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Pairs<'a, T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (pos, val) = self.iter.next_back()?;
+        Some((self.slots.value_id(self.offset + pos), val))
+    }
+
+    #[inline]
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let (pos, val) = self.iter.nth_back(n)?;
+        Some((self.slots.value_id(self.offset + pos), val))
     }
 }
 
+impl<'a, T> ExactSizeIterator for Pairs<'a, T> {}
+
+impl<'a, T> FusedIterator for Pairs<'a, T> {}
+
 /// Mutable iterator over an arena's ID/value pairs.
 ///
 /// This struct is created by the [`pairs_mut`](Arena::pairs_mut) method on [`Arena`].
 pub struct PairsMut<'a, T> {
     iter: std::iter::Enumerate<std::slice::IterMut<'a, T>>,
-    slots: &'a [Slot],
-    #[cfg(feature = "uuid")]
-    uuid: Uuid,
+    // position of the first value in the iterated slice
+    offset: usize,
+    slots: SlotIds<'a, T>,
 }
 
 impl<'a, T> Iterator for PairsMut<'a, T> {
@@ -1328,59 +2097,297 @@ impl<'a, T> Iterator for PairsMut<'a, T> {
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        let (idx, val) = self.iter.next()?;
-        let idx = self.slots[idx].value_slot;
-        match &self.slots[idx].state {
-            State::Used { uid, .. } => Some((
-                ArenaId::<T> {
-                    #[cfg(feature = "uuid")]
-                    uuid: self.uuid,
-                    uid: *uid,
-                    idx,
-                    _ty: PhantomData,
-                },
-                val,
-            )),
-            _ => unreachable!(),
+        let (pos, val) = self.iter.next()?;
+        Some((self.slots.value_id(self.offset + pos), val))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let (pos, val) = self.iter.nth(n)?;
+        Some((self.slots.value_id(self.offset + pos), val))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for PairsMut<'a, T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (pos, val) = self.iter.next_back()?;
+        Some((self.slots.value_id(self.offset + pos), val))
+    }
+
+    #[inline]
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let (pos, val) = self.iter.nth_back(n)?;
+        Some((self.slots.value_id(self.offset + pos), val))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for PairsMut<'a, T> {}
+
+impl<'a, T> FusedIterator for PairsMut<'a, T> {}
+
+/// Iterator over mutable chunks of an arena's ID/value pairs.
+///
+/// This struct is created by the [`chunks_pairs_mut`](Arena::chunks_pairs_mut) method on [`Arena`].
+pub struct ChunksPairsMut<'a, T> {
+    iter: std::iter::Enumerate<std::slice::ChunksMut<'a, T>>,
+    chunk_size: usize,
+    slots: SlotIds<'a, T>,
+}
+
+impl<'a, T> ChunksPairsMut<'a, T> {
+    #[inline]
+    fn chunk(&self, (i, values): (usize, &'a mut [T])) -> PairsMut<'a, T> {
+        PairsMut {
+            iter: values.iter_mut().enumerate(),
+            offset: i * self.chunk_size,
+            slots: self.slots,
         }
     }
 }
 
-/// Iterator over an arena's IDs.
+impl<'a, T> Iterator for ChunksPairsMut<'a, T> {
+    type Item = PairsMut<'a, T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.iter.next()?;
+        Some(self.chunk(chunk))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let chunk = self.iter.nth(n)?;
+        Some(self.chunk(chunk))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for ChunksPairsMut<'a, T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let chunk = self.iter.next_back()?;
+        Some(self.chunk(chunk))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for ChunksPairsMut<'a, T> {}
+
+impl<'a, T> FusedIterator for ChunksPairsMut<'a, T> {}
+
+/// Owning iterator over an arena's ID/value pairs.
 ///
-/// This struct is created by the [`ids`](Arena::ids) method on [`Arena`].
-pub struct Ids<'a, T> {
-    iter: std::iter::Enumerate<std::slice::Iter<'a, Slot>>,
-    _ty: PhantomData<T>,
+/// This struct is created by the [`into_pairs`](Arena::into_pairs) method on [`Arena`].
+pub struct IntoPairs<T> {
+    iter: std::iter::Enumerate<std::vec::IntoIter<T>>,
+    slots: Vec<Slot>,
     #[cfg(feature = "uuid")]
     uuid: Uuid,
 }
 
-impl<'a, T> Iterator for Ids<'a, T> {
-    type Item = ArenaId<T>;
+impl<T> IntoPairs<T> {
+    #[inline]
+    fn slot_ids(&self) -> SlotIds<'_, T> {
+        SlotIds {
+            slots: &self.slots,
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<T> Iterator for IntoPairs<T> {
+    type Item = (ArenaId<T>, T);
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        let (idx, slot) = self.iter.next()?;
-        match &slot.state {
-            State::Used { uid, .. } => Some(ArenaId::<T> {
+        let (pos, val) = self.iter.next()?;
+        Some((self.slot_ids().value_id(pos), val))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let (pos, val) = self.iter.nth(n)?;
+        Some((self.slot_ids().value_id(pos), val))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoPairs<T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (pos, val) = self.iter.next_back()?;
+        Some((self.slot_ids().value_id(pos), val))
+    }
+
+    #[inline]
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let (pos, val) = self.iter.nth_back(n)?;
+        Some((self.slot_ids().value_id(pos), val))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoPairs<T> {}
+
+impl<T> FusedIterator for IntoPairs<T> {}
+
+// Looks up the IDs of used slots for the arena's iterators.
+struct SlotIds<'a, T> {
+    slots: &'a [Slot],
+    #[cfg(feature = "uuid")]
+    uuid: Uuid,
+    _ty: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> Clone for SlotIds<'a, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for SlotIds<'a, T> {}
+
+// Splits a range of positions into its start and its bounds for slicing.
+#[inline]
+fn range_bounds<R: RangeBounds<usize>>(range: R) -> (usize, (Bound<usize>, Bound<usize>)) {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    (
+        start,
+        (range.start_bound().cloned(), range.end_bound().cloned()),
+    )
+}
+
+impl<'a, T> SlotIds<'a, T> {
+    #[inline]
+    fn id(&self, idx: usize) -> ArenaId<T> {
+        match &self.slots[idx].state {
+            State::Used { uid, .. } => ArenaId::<T> {
                 #[cfg(feature = "uuid")]
                 uuid: self.uuid,
                 uid: *uid,
                 idx,
                 _ty: PhantomData,
-            }),
-            _ => None,
+            },
+            _ => unreachable!(),
         }
     }
+
+    // the ID of the slot assigned to the value at `pos`
+    #[inline]
+    fn value_id(&self, pos: usize) -> ArenaId<T> {
+        self.id(self.slots[pos].value_slot)
+    }
+}
+
+/// Iterator over an arena's IDs.
+///
+/// This struct is created by the [`ids`](Arena::ids) method on [`Arena`].
+pub struct Ids<'a, T> {
+    iter: std::slice::Iter<'a, Slot>,
+    slots: SlotIds<'a, T>,
+}
+
+impl<'a, T> Iterator for Ids<'a, T> {
+    type Item = ArenaId<T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.iter.next()?;
+        Some(self.slots.id(slot.value_slot))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let slot = self.iter.nth(n)?;
+        Some(self.slots.id(slot.value_slot))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Ids<'a, T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let slot = self.iter.next_back()?;
+        Some(self.slots.id(slot.value_slot))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Ids<'a, T> {}
+
+impl<'a, T> FusedIterator for Ids<'a, T> {}
+
+/// Iterator over an arena's IDs in slot order.
+///
+/// This struct is created by the [`ids_by_slot`](Arena::ids_by_slot) method on [`Arena`].
+pub struct IdsBySlot<'a, T> {
+    iter: std::iter::Enumerate<std::slice::Iter<'a, Slot>>,
+    remaining: usize,
+    slots: SlotIds<'a, T>,
+}
+
+impl<'a, T> Iterator for IdsBySlot<'a, T> {
+    type Item = ArenaId<T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (idx, _) = self
+            .iter
+            .find(|(_, slot)| matches!(slot.state, State::Used { .. }))?;
+        self.remaining -= 1;
+        Some(self.slots.id(idx))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for IdsBySlot<'a, T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (idx, _) = self
+            .iter
+            .rfind(|(_, slot)| matches!(slot.state, State::Used { .. }))?;
+        self.remaining -= 1;
+        Some(self.slots.id(idx))
+    }
 }
 
+impl<'a, T> ExactSizeIterator for IdsBySlot<'a, T> {}
+
+impl<'a, T> FusedIterator for IdsBySlot<'a, T> {}
+
 #[cfg(feature = "serde")]
 mod ser {
-    use crate::State;
-    use serde::de::Visitor;
+    use crate::{Arena, ArenaId, Slot, State};
+    use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
     use serde::ser::SerializeStruct;
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::{HashMap, HashSet};
     use std::fmt::Formatter;
 
     impl<T: Serialize> Serialize for crate::Arena<T> {
@@ -1410,48 +2417,59 @@ mod ser {
         where
             D: Deserializer<'de>,
         {
-            let mut de: DeArena<T> = DeArena::deserialize(deserializer)?;
-
-            de.entries.sort_by(|a, b| a.idx.cmp(&b.idx));
-
-            let mut slots = Vec::new();
-            let mut next_value_slot = 0;
-            let mut next_value = 0;
-
-            let mut first_free = None;
-            for e in &de.entries {
-                // push free slots until we reach the entry's index
-                while slots.len() < e.idx {
-                    slots.push(crate::Slot {
-                        value_slot: de.entries[next_value_slot].idx,
-                        state: crate::State::Free {
-                            next_free: first_free.replace(slots.len()),
-                        },
+            let de: DeArena<T> = DeArena::deserialize(deserializer)?;
+            let entries = de.entries.into_iter().map(|e| (e.idx, e.uid, e.val));
+            Self::from_entries(de.next_uid, entries).map_err(de::Error::custom)
+        }
+    }
+
+    impl<T> Arena<T> {
+        // Builds an arena from ID/value pairs given in value order, such as the
+        // ones yielded by `pairs()`. Slots not used by any entry become free.
+        pub(crate) fn from_entries<I>(next_uid: u64, entries: I) -> Result<Self, &'static str>
+        where
+            I: IntoIterator<Item = (usize, u64, T)>,
+        {
+            let mut arena = Self::new();
+            let mut uids = HashSet::new();
+            for (idx, uid, val) in entries {
+                if uid >= next_uid {
+                    return Err("entry uid is not below next_uid");
+                }
+                if !uids.insert(uid) {
+                    return Err("duplicate entry uid");
+                }
+                if idx >= arena.slots.len() {
+                    arena.slots.resize_with(idx + 1, || Slot {
+                        value_slot: 0,
+                        state: State::Free { next_free: None },
                     });
-                    next_value_slot += 1;
                 }
-
-                // insert the entry
-                slots.push(crate::Slot {
-                    value_slot: de.entries[next_value_slot].idx,
-                    state: crate::State::Used {
-                        uid: e.uid,
-                        value: next_value,
-                    },
-                });
-
-                next_value_slot += 1;
-                next_value += 1;
+                if let State::Used { .. } = arena.slots[idx].state {
+                    return Err("duplicate entry slot index");
+                }
+                arena.slots[idx].state = State::Used {
+                    uid,
+                    value: arena.values.len(),
+                };
+                arena.values.push(val);
             }
 
-            let values = de.entries.into_iter().map(|e| e.val).collect();
+            // link up the value slots and the free list
+            for idx in 0..arena.slots.len() {
+                match arena.slots[idx].state {
+                    State::Used { value, .. } => arena.slots[value].value_slot = idx,
+                    State::Free { .. } => {
+                        arena.slots[idx].state = State::Free {
+                            next_free: arena.first_free.replace(idx),
+                        };
+                    }
+                    State::Reserved { .. } => unreachable!(),
+                }
+            }
 
-            Ok(Self {
-                next_uid: de.next_uid,
-                slots,
-                values,
-                first_free,
-            })
+            arena.next_uid = next_uid;
+            Ok(arena)
         }
     }
 
@@ -1474,8 +2492,346 @@ mod ser {
         next_uid: u64,
         entries: Vec<DeEntry<T>>,
     }
+
+    impl<T> Serialize for ArenaId<T> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let mut s = serializer.serialize_struct("ArenaId", 2)?;
+            s.serialize_field("uid", &self.uid)?;
+            s.serialize_field("idx", &self.idx)?;
+            s.end()
+        }
+    }
+
+    // IDs don't serialize the arena's UUID, so deserialized IDs get a nil one
+    // and have to be remapped (see `RemapIds`) before they will match an arena.
+    impl<'de, T> Deserialize<'de> for ArenaId<T> {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let de = DeId::deserialize(deserializer)?;
+            Ok(ArenaId {
+                #[cfg(feature = "uuid")]
+                uuid: uuid::Uuid::nil(),
+                uid: de.uid,
+                idx: de.idx,
+                _ty: std::marker::PhantomData,
+            })
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename = "ArenaId")]
+    struct DeId {
+        uid: u64,
+        idx: usize,
+    }
+
+    /// Deserializes a serialized arena into an existing arena, rather than
+    /// creating a new one.
+    ///
+    /// Every loaded value is inserted into the target arena and assigned a fresh
+    /// ID. The IDs the values had when they were serialized are mapped to their
+    /// new ones in the returned [`IdRemap`], which can then be used to fix up any
+    /// IDs that the values hold (see [`RemapIds`]).
+    ///
+    /// If deserialization fails part way through, the values that were already
+    /// inserted are removed again, leaving the arena's values as they were.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::{Arena, ArenaSeed};
+    /// use serde::de::DeserializeSeed;
+    ///
+    /// let mut prefab = Arena::new();
+    /// let a = prefab.insert('A');
+    /// let b = prefab.insert('B');
+    /// let json = serde_json::to_string(&prefab).unwrap();
+    ///
+    /// let mut world = Arena::new();
+    /// world.insert('X');
+    ///
+    /// let mut de = serde_json::Deserializer::from_str(&json);
+    /// let remap = ArenaSeed::new(&mut world).deserialize(&mut de).unwrap();
+    ///
+    /// assert_eq!(world.as_slice(), &['X', 'A', 'B']);
+    /// assert_eq!(world.get(remap.get(a).unwrap()), Some(&'A'));
+    /// assert_eq!(world.get(remap.get(b).unwrap()), Some(&'B'));
+    /// ```
+    pub struct ArenaSeed<'a, T> {
+        arena: &'a mut Arena<T>,
+    }
+
+    impl<'a, T> ArenaSeed<'a, T> {
+        /// Creates a seed that will insert deserialized values into `arena`.
+        #[inline]
+        pub fn new(arena: &'a mut Arena<T>) -> Self {
+            Self { arena }
+        }
+    }
+
+    impl<'de, 'a, T: Deserialize<'de>> DeserializeSeed<'de> for ArenaSeed<'a, T> {
+        type Value = IdRemap<T>;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_struct("Arena", &["next_uid", "entries"], self)
+        }
+    }
+
+    impl<'de, 'a, T: Deserialize<'de>> Visitor<'de> for ArenaSeed<'a, T> {
+        type Value = IdRemap<T>;
+
+        fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+            f.write_str("struct Arena")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            seq.next_element::<IgnoredAny>()?
+                .ok_or_else(|| de::Error::invalid_length(0, &"struct Arena"))?;
+            let mut remap = IdRemap::new();
+            seq.next_element_seed(EntriesSeed {
+                arena: self.arena,
+                remap: &mut remap,
+            })?
+            .ok_or_else(|| de::Error::invalid_length(1, &"struct Arena"))?;
+            Ok(remap)
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut remap = None;
+            while let Some(field) = map.next_key::<Field>()? {
+                match field {
+                    Field::Entries => {
+                        if remap.is_some() {
+                            return Err(de::Error::duplicate_field("entries"));
+                        }
+                        let mut ids = IdRemap::new();
+                        map.next_value_seed(EntriesSeed {
+                            arena: &mut *self.arena,
+                            remap: &mut ids,
+                        })?;
+                        remap = Some(ids);
+                    }
+                    Field::NextUid | Field::Other => {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                }
+            }
+            remap.ok_or_else(|| de::Error::missing_field("entries"))
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(field_identifier, rename_all = "snake_case")]
+    enum Field {
+        NextUid,
+        Entries,
+        #[serde(other)]
+        Other,
+    }
+
+    struct EntriesSeed<'a, 'b, T> {
+        arena: &'a mut Arena<T>,
+        remap: &'b mut IdRemap<T>,
+    }
+
+    impl<'de, 'a, 'b, T: Deserialize<'de>> DeserializeSeed<'de> for EntriesSeed<'a, 'b, T> {
+        type Value = ();
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_seq(self)
+        }
+    }
+
+    impl<'de, 'a, 'b, T: Deserialize<'de>> Visitor<'de> for EntriesSeed<'a, 'b, T> {
+        type Value = ();
+
+        fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+            f.write_str("a sequence of arena entries")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let err = loop {
+                match seq.next_element::<DeEntry<T>>() {
+                    Ok(Some(e)) => {
+                        // a duplicate would overwrite the first value's entry in
+                        // the remap, so that it could never be removed again
+                        if self.remap.ids.contains_key(&(e.uid, e.idx)) {
+                            break de::Error::custom("duplicate entry ID");
+                        }
+                        let id = self.arena.insert(e.val);
+                        self.remap.ids.insert((e.uid, e.idx), id);
+                    }
+                    Ok(None) => return Ok(()),
+                    Err(err) => break err,
+                }
+            };
+
+            // don't leave a partially loaded arena behind
+            for (_, id) in self.remap.ids.drain() {
+                self.arena.remove(id);
+            }
+            Err(err)
+        }
+    }
+
+    /// A mapping from the IDs values had in a serialized arena to the IDs they
+    /// were assigned when loaded into another arena with [`ArenaSeed`].
+    #[derive(Debug)]
+    pub struct IdRemap<T> {
+        ids: HashMap<(u64, usize), ArenaId<T>>,
+    }
+
+    impl<T> IdRemap<T> {
+        fn new() -> Self {
+            Self {
+                ids: HashMap::new(),
+            }
+        }
+
+        /// Returns the new ID of the value that was serialized with the `old` ID,
+        /// or `None` if no such value was loaded.
+        #[inline]
+        pub fn get(&self, old: ArenaId<T>) -> Option<ArenaId<T>> {
+            self.ids.get(&(old.uid, old.idx)).copied()
+        }
+
+        /// Returns the number of values that were loaded.
+        #[inline]
+        pub fn len(&self) -> usize {
+            self.ids.len()
+        }
+
+        /// Returns `true` if no values were loaded.
+        #[inline]
+        pub fn is_empty(&self) -> bool {
+            self.ids.is_empty()
+        }
+
+        /// Returns an iterator over the new IDs of all loaded values, in no
+        /// particular order.
+        #[inline]
+        pub fn new_ids(&self) -> impl Iterator<Item = ArenaId<T>> + '_ {
+            self.ids.values().copied()
+        }
+
+        /// Calls [`remap_ids`](RemapIds::remap_ids) on every loaded value, so that
+        /// the IDs they hold refer to the other loaded values.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// # use arena::{Arena, ArenaId, ArenaSeed, IdRemap, RemapIds};
+        /// use serde::de::DeserializeSeed;
+        /// use serde::{Deserialize, Serialize};
+        ///
+        /// #[derive(Debug, Serialize, Deserialize)]
+        /// struct Node {
+        ///     parent: Option<ArenaId<Node>>,
+        /// }
+        ///
+        /// impl RemapIds<Node> for Node {
+        ///     fn remap_ids(&mut self, remap: &IdRemap<Node>) {
+        ///         self.parent.remap_ids(remap);
+        ///     }
+        /// }
+        ///
+        /// let mut prefab = Arena::new();
+        /// let root = prefab.insert(Node { parent: None });
+        /// let child = prefab.insert(Node { parent: Some(root) });
+        /// let json = serde_json::to_string(&prefab).unwrap();
+        ///
+        /// let mut world = Arena::new();
+        /// world.insert(Node { parent: None });
+        ///
+        /// let mut de = serde_json::Deserializer::from_str(&json);
+        /// let remap = ArenaSeed::new(&mut world).deserialize(&mut de).unwrap();
+        /// remap.remap_values(&mut world);
+        ///
+        /// let new_root = remap.get(root).unwrap();
+        /// let new_child = remap.get(child).unwrap();
+        /// assert_eq!(world[new_child].parent, Some(new_root));
+        /// ```
+        pub fn remap_values(&self, arena: &mut Arena<T>)
+        where
+            T: RemapIds<T>,
+        {
+            for id in self.ids.values() {
+                if let Some(val) = arena.get_mut(*id) {
+                    val.remap_ids(self);
+                }
+            }
+        }
+    }
+
+    /// Implemented by values that hold IDs into an arena of `T`, so that those
+    /// IDs can be rewritten after the arena was loaded with [`ArenaSeed`].
+    ///
+    /// IDs that are not found in the remap are left unchanged.
+    pub trait RemapIds<T> {
+        /// Replaces every ID held by `self` with its remapped ID.
+        fn remap_ids(&mut self, remap: &IdRemap<T>);
+    }
+
+    impl<T> RemapIds<T> for ArenaId<T> {
+        #[inline]
+        fn remap_ids(&mut self, remap: &IdRemap<T>) {
+            if let Some(id) = remap.get(*self) {
+                *self = id;
+            }
+        }
+    }
+
+    impl<T, R: RemapIds<T>> RemapIds<T> for Option<R> {
+        #[inline]
+        fn remap_ids(&mut self, remap: &IdRemap<T>) {
+            if let Some(val) = self {
+                val.remap_ids(remap);
+            }
+        }
+    }
+
+    impl<T, R: RemapIds<T>> RemapIds<T> for Vec<R> {
+        #[inline]
+        fn remap_ids(&mut self, remap: &IdRemap<T>) {
+            for val in self {
+                val.remap_ids(remap);
+            }
+        }
+    }
 }
 
+#[cfg(feature = "serde")]
+pub use ser::{ArenaSeed, IdRemap, RemapIds};
+
+#[cfg(feature = "json")]
+mod json;
+
+#[cfg(any(feature = "arbitrary", feature = "proptest"))]
+mod fuzz;
+
+#[cfg(feature = "proptest")]
+pub mod strategy;
+
 #[test]
 fn rain_test() {
     let mut arena = Arena::new();
@@ -0,0 +1,652 @@
+//! Patches that bring one arena up to date with another.
+
+use crate::{Arena, ArenaId, Change, Slot, State};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+impl<T: PartialEq + Clone> Arena<T> {
+    /// Returns a patch which, when [applied](Arena::apply_patch) to this arena,
+    /// makes it identical to `other`.
+    ///
+    /// Entries are matched up by the slot index and uid of their IDs. Entries
+    /// only in this arena are removed by the patch, ones only in `other` are
+    /// added, and ones in both whose values differ are changed, so the patch
+    /// holds a clone of every added and changed value. It also holds the changes
+    /// to the layout of the slot table: the positions whose values moved, and the
+    /// free slots and reserved IDs that changed. So the size of the patch grows
+    /// with the number of changes, not with the size of the arenas.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut server = Arena::new();
+    /// let a = server.insert('A');
+    /// let b = server.insert('B');
+    /// let mut client = server.clone();
+    ///
+    /// server[a] = 'Z';
+    /// server.remove(b);
+    /// let c = server.insert('C');
+    ///
+    /// let patch = client.diff(&server);
+    /// assert!(patch.removed().eq([b]));
+    /// assert!(patch.changed().eq([(a, &'Z')]));
+    /// assert!(patch.added().eq([(c, &'C')]));
+    ///
+    /// client.apply_patch(patch).unwrap();
+    /// assert_eq!(client.as_slice(), &['Z', 'C']);
+    /// assert_eq!(client.get(c), Some(&'C'));
+    /// assert_eq!(client.insert('D'), server.insert('D'));
+    /// ```
+    pub fn diff(&self, other: &Self) -> ArenaPatch<T> {
+        let mut removed = Vec::new();
+        let mut changed = Vec::new();
+        for (id, val) in self.pairs() {
+            let key = Key::of(id);
+            match other.value_at(key) {
+                Some(other_val) if other_val == val => {}
+                Some(other_val) => changed.push((key, other_val.clone())),
+                None => removed.push(key),
+            }
+        }
+
+        let added = other
+            .pairs()
+            .map(|(id, val)| (Key::of(id), val))
+            .filter(|&(key, _)| self.value_at(key).is_none())
+            .map(|(key, val)| (key, val.clone()))
+            .collect();
+
+        // the parts of the layout that differ from this arena's
+        let moved = (0..other.len())
+            .map(|pos| (pos, other.slots[pos].value_slot))
+            .filter(|&(pos, idx)| pos >= self.len() || self.slots[pos].value_slot != idx)
+            .collect();
+        let free_links = other
+            .free_list()
+            .map(|idx| match other.slots[idx].state {
+                State::Free { next_free } => (idx, next_free),
+                _ => unreachable!(),
+            })
+            .filter(|&(idx, next_free)| {
+                !matches!(self.slots.get(idx), Some(Slot { state: State::Free { next_free: next }, .. }) if *next == next_free)
+            })
+            .collect();
+        let reserved = other
+            .reserved_keys()
+            .filter(|&key| !self.is_reserved_key(key))
+            .collect();
+        let unreserved = self
+            .reserved_keys()
+            .filter(|&key| !other.is_reserved_key(key))
+            .collect();
+
+        ArenaPatch {
+            next_uid: other.next_uid,
+            len: other.len(),
+            slot_count: other.slots.len(),
+            removed,
+            changed,
+            added,
+            moved,
+            first_free: other.first_free,
+            free_links,
+            reserved,
+            unreserved,
+            #[cfg(feature = "uuid")]
+            uuid: other.uuid,
+        }
+    }
+}
+
+impl<T> Arena<T> {
+    /// Applies a patch made by [`diff`](Arena::diff), making this arena identical
+    /// to the one the patch was made to: the same values in the same order, with
+    /// the same IDs, and the same free slots, so it will also assign the same IDs
+    /// to new values.
+    ///
+    /// The arena must be in the same state as the one the patch was made from.
+    /// If the patch removes or changes entries that aren't in the arena, or the
+    /// arena's layout doesn't fit the patch, an error is returned and the arena
+    /// is left as it was. Other differences aren't detected, since the patch
+    /// only holds what changed.
+    ///
+    /// The patch is applied in place, changing only the values and slots that
+    /// it changes, so the time it takes grows with the size of the patch rather
+    /// than with the size of the arena, apart from checking the free list.
+    ///
+    /// With the `uuid` feature, the arena also takes on the UUID of the arena
+    /// the patch was made to, unless the patch was deserialized, since
+    /// serialized patches don't hold the UUID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::{Arena, PatchError};
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    /// let old = arena.clone();
+    ///
+    /// arena.remove(a);
+    /// let patch = old.diff(&arena);
+    ///
+    /// let mut copy = old.clone();
+    /// copy.apply_patch(patch.clone()).unwrap();
+    /// assert!(copy.is_empty());
+    ///
+    /// // the patch has already been applied, so `a` can't be removed again
+    /// assert_eq!(copy.apply_patch(patch), Err(PatchError::BaseMismatch));
+    /// ```
+    pub fn apply_patch(&mut self, patch: ArenaPatch<T>) -> Result<(), PatchError> {
+        let plan = self.plan_patch(&patch)?;
+        let ArenaPatch {
+            next_uid,
+            len,
+            slot_count,
+            removed,
+            changed,
+            added,
+            mut moved,
+            first_free,
+            #[cfg(feature = "uuid")]
+            uuid,
+            ..
+        } = patch;
+        let old_len = self.len();
+        let recording = self.is_recording_changes();
+        let removed: Vec<_> = match recording {
+            true => removed.into_iter().map(|key| self.key_id(key)).collect(),
+            false => Vec::new(),
+        };
+        let modified: Vec<_> = changed.iter().map(|(key, _)| key.idx).collect();
+
+        // the values of entries that stay, or are added to a slot in the same
+        // position as the entry they replace, are replaced where they are
+        for (key, val) in changed {
+            let pos = self.position_of(key.idx);
+            self.value_replace(pos, self.key_id(key), val);
+        }
+        let mut added: HashMap<usize, T> =
+            added.into_iter().map(|(key, val)| (key.idx, val)).collect();
+        for &(pos, idx) in &plan.in_place {
+            let id = self.slot_ids().id(idx);
+            self.value_replace(pos, id, added.remove(&idx).unwrap());
+        }
+
+        // take out the values past the new end, by the slot they were in
+        let mut taken = HashMap::new();
+        while self.values.len() > len {
+            let idx = self.slots[self.values.len() - 1].value_slot;
+            taken.insert(idx, self.values_pop().unwrap());
+        }
+
+        // Then fill the positions whose slot changes, in order. A value that is
+        // still in the arena is swapped into place, and the value it displaces
+        // takes its old position, which comes later, since the earlier ones are
+        // already filled.
+        moved.sort_unstable();
+        let mut at: HashMap<usize, usize> = moved
+            .iter()
+            .filter(|&&(pos, _)| pos < self.values.len())
+            .map(|&(pos, _)| (pos, self.slots[pos].value_slot))
+            .collect();
+        let mut pos_of: HashMap<usize, usize> = at.iter().map(|(&pos, &idx)| (idx, pos)).collect();
+        for &(pos, idx) in &moved {
+            let val = match (added.remove(&idx), pos_of.remove(&idx)) {
+                (Some(val), _) => val,
+                (None, Some(from)) => {
+                    self.values_swap(pos, from);
+                    let displaced = at.remove(&pos).unwrap();
+                    at.insert(from, displaced);
+                    pos_of.insert(displaced, from);
+                    continue;
+                }
+                (None, None) => taken.remove(&idx).unwrap(),
+            };
+            match at.remove(&pos) {
+                Some(displaced) => {
+                    pos_of.remove(&displaced);
+                    let id = self.slot_ids().id(displaced);
+                    let old = self.value_replace(pos, id, val);
+                    taken.insert(displaced, old);
+                }
+                None => self.values_push(val),
+            }
+        }
+        // what's left are the values of removed entries
+        drop(taken);
+
+        while self.slots.len() < slot_count {
+            self.slots_push(Slot {
+                value_slot: 0,
+                state: State::Free { next_free: None },
+            });
+        }
+        for (&idx, state) in &plan.states {
+            self.slot_mut(idx).state = state.clone();
+        }
+        for &(pos, idx) in &moved {
+            self.slot_mut(pos).value_slot = idx;
+        }
+        while self.slots.len() > slot_count {
+            self.slots_pop();
+        }
+        self.next_uid = next_uid;
+        self.first_free = first_free;
+        self.reserved = plan.reserved;
+        #[cfg(feature = "uuid")]
+        if !uuid.is_nil() {
+            self.uuid = uuid;
+        }
+
+        if recording {
+            self.log_patch(old_len, removed, &moved, &plan, modified);
+        }
+        self.debug_check();
+        Ok(())
+    }
+
+    // Records the changes made by applying a patch, as the change log would
+    // have if they had been made one at a time: removals first, then the
+    // entries that were added or moved, in the order of their new positions.
+    fn log_patch(
+        &mut self,
+        old_len: usize,
+        removed: Vec<ArenaId<T>>,
+        moved: &[(usize, usize)],
+        plan: &Plan,
+        modified: Vec<usize>,
+    ) {
+        let mut len = old_len - removed.len();
+        for id in removed {
+            self.log_change(|_| Change::Removed(id));
+        }
+
+        let mut entered: Vec<_> = moved.iter().chain(&plan.in_place).copied().collect();
+        entered.sort_unstable();
+        for (pos, idx) in entered {
+            let id = self.slot_ids().id(idx);
+            let from = plan.moved_from.get(&idx).copied().unwrap_or_else(|| {
+                self.log_change(|_| Change::Inserted(id));
+                len += 1;
+                len - 1
+            });
+            if from != pos {
+                self.log_change(|_| Change::Moved { id, from, to: pos });
+            }
+        }
+
+        for idx in modified {
+            self.log_change(|arena| Change::Modified(arena.slot_ids().id(idx)));
+        }
+    }
+
+    // Checks that the patch can be applied to the arena, without changing it,
+    // and works out the changes to the slot table that applying it makes. This
+    // only looks at the entries and slots that the patch changes, and at the
+    // free list.
+    fn plan_patch(&self, patch: &ArenaPatch<T>) -> Result<Plan, PatchError> {
+        let old_len = self.len();
+        let (len, slot_count) = (patch.len, patch.slot_count);
+
+        let mut touched = HashSet::new();
+        let changed = patch.changed.iter().map(|(key, _)| key);
+        for key in patch.removed.iter().chain(changed) {
+            if self.value_at(*key).is_none() || !touched.insert(key.idx) {
+                return Err(PatchError::BaseMismatch);
+            }
+        }
+        let removed: HashSet<_> = patch.removed.iter().map(|key| key.idx).collect();
+        let mut unreserved = HashSet::new();
+        for &key in &patch.unreserved {
+            if !self.is_reserved_key(key) || !unreserved.insert(key.idx) {
+                return Err(PatchError::BaseMismatch);
+            }
+        }
+        // whether the slot is used or reserved, and stays that way
+        let stays = |idx: usize| match self.slots.get(idx).map(|slot| &slot.state) {
+            Some(State::Used { .. }) => !removed.contains(&idx),
+            Some(State::Reserved { .. }) => !unreserved.contains(&idx),
+            _ => false,
+        };
+
+        if len > slot_count || len + patch.removed.len() != old_len + patch.added.len() {
+            return Err(PatchError::Invalid);
+        }
+        let mut added = HashMap::new();
+        for (key, _) in &patch.added {
+            if stays(key.idx) {
+                return Err(PatchError::BaseMismatch);
+            }
+            if key.uid >= patch.next_uid || added.insert(key.idx, key.uid).is_some() {
+                return Err(PatchError::Invalid);
+            }
+        }
+
+        // the slot of each value whose position changes, and its new position
+        let mut positions = HashMap::new();
+        let mut placed = HashMap::new();
+        for &(pos, idx) in &patch.moved {
+            if pos >= len
+                || positions.insert(pos, idx).is_some()
+                || placed.insert(idx, pos).is_some()
+            {
+                return Err(PatchError::Invalid);
+            }
+        }
+        if (old_len..len).any(|pos| !positions.contains_key(&pos)) {
+            return Err(PatchError::Invalid);
+        }
+        // a position that keeps its slot, but whose entry is removed, must be
+        // filled by an entry that is added to the same slot
+        let mut in_place = Vec::new();
+        for key in &patch.removed {
+            let pos = self.position_of(key.idx);
+            if pos < len && !positions.contains_key(&pos) {
+                if !added.contains_key(&key.idx) || placed.insert(key.idx, pos).is_some() {
+                    return Err(PatchError::Invalid);
+                }
+                in_place.push((pos, key.idx));
+            }
+        }
+        // and the values that stay, but leave their position, must be moved
+        let vacated = positions.keys().copied().filter(|&pos| pos < old_len);
+        for pos in vacated.chain(len..old_len) {
+            let idx = self.slots[pos].value_slot;
+            if !removed.contains(&idx) && !placed.contains_key(&idx) {
+                return Err(PatchError::Invalid);
+            }
+        }
+        if added.keys().any(|idx| !placed.contains_key(idx)) {
+            return Err(PatchError::Invalid);
+        }
+
+        let mut states = HashMap::new();
+        let mut moved_from = HashMap::new();
+        for (&idx, &pos) in &placed {
+            let uid = match (added.get(&idx), self.slots.get(idx).map(|slot| &slot.state)) {
+                (Some(&uid), _) => uid,
+                (None, Some(&State::Used { uid, value })) if stays(idx) => {
+                    // the value's old position must be taken by another
+                    if value < len && !positions.contains_key(&value) {
+                        return Err(PatchError::Invalid);
+                    }
+                    moved_from.insert(idx, value);
+                    uid
+                }
+                _ => return Err(PatchError::Invalid),
+            };
+            if idx >= slot_count {
+                return Err(PatchError::Invalid);
+            }
+            states.insert(idx, State::Used { uid, value: pos });
+        }
+        let reserved = patch
+            .reserved
+            .iter()
+            .map(|key| (key.idx, State::Reserved { uid: key.uid }));
+        let free = patch
+            .free_links
+            .iter()
+            .map(|&(idx, next_free)| (idx, State::Free { next_free }));
+        for (idx, state) in reserved.chain(free) {
+            if stays(idx) {
+                return Err(PatchError::BaseMismatch);
+            }
+            if matches!(state, State::Reserved { uid } if uid >= patch.next_uid)
+                || idx >= slot_count
+                || states.insert(idx, state).is_some()
+            {
+                return Err(PatchError::Invalid);
+            }
+        }
+
+        // every slot that is removed, unreserved or added needs a new state,
+        // unless it's dropped from the end of the slot table
+        let old_slots = self.slots.len();
+        let leaving = removed.iter().chain(&unreserved).copied();
+        if leaving
+            .chain(old_slots..slot_count)
+            .any(|idx| idx < slot_count && !states.contains_key(&idx))
+            || (slot_count..old_slots).any(stays)
+        {
+            return Err(PatchError::Invalid);
+        }
+        // the uids of the entries that stay are only known to be below the
+        // arena's own next uid
+        if patch.next_uid < self.next_uid
+            && self
+                .slots
+                .iter()
+                .enumerate()
+                .any(|(idx, slot)| match slot.state {
+                    State::Used { uid, .. } | State::Reserved { uid } => {
+                        stays(idx) && uid >= patch.next_uid
+                    }
+                    State::Free { .. } => false,
+                })
+        {
+            return Err(PatchError::Invalid);
+        }
+
+        // the free list must link up exactly the slots that are left
+        let reserved = self.reserved - unreserved.len() + patch.reserved.len();
+        let free_count = slot_count
+            .checked_sub(len + reserved)
+            .ok_or(PatchError::Invalid)?;
+        let mut visited = HashSet::new();
+        let mut next = patch.first_free;
+        while let Some(idx) = next {
+            let state = match states.get(&idx) {
+                Some(state) => Some(state),
+                None => self
+                    .slots
+                    .get(idx)
+                    .filter(|_| idx < slot_count)
+                    .map(|slot| &slot.state),
+            };
+            next = match state {
+                Some(State::Free { next_free }) if visited.insert(idx) => *next_free,
+                Some(State::Free { .. }) => return Err(PatchError::Invalid),
+                _ => return Err(PatchError::BaseMismatch),
+            };
+        }
+        if visited.len() != free_count {
+            return Err(PatchError::Invalid);
+        }
+
+        Ok(Plan {
+            states,
+            moved_from,
+            in_place,
+            reserved,
+        })
+    }
+
+    // the position of the value in the used slot
+    fn position_of(&self, idx: usize) -> usize {
+        match self.slots[idx].state {
+            State::Used { value, .. } => value,
+            _ => unreachable!(),
+        }
+    }
+
+    fn key_id(&self, key: Key) -> ArenaId<T> {
+        ArenaId {
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+            uid: key.uid,
+            idx: key.idx,
+            _ty: PhantomData,
+        }
+    }
+
+    fn reserved_keys(&self) -> impl Iterator<Item = Key> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, slot)| match slot.state {
+                State::Reserved { uid } => Some(Key { idx, uid }),
+                _ => None,
+            })
+    }
+
+    fn is_reserved_key(&self, key: Key) -> bool {
+        matches!(self.slots.get(key.idx), Some(Slot { state: State::Reserved { uid }, .. }) if *uid == key.uid)
+    }
+
+    // the value of the live entry with the key, if there is one
+    fn value_at(&self, key: Key) -> Option<&T> {
+        match self.slots.get(key.idx)?.state {
+            State::Used { uid, value } if uid == key.uid => Some(&self.values[value]),
+            _ => None,
+        }
+    }
+}
+
+/// The differences between two arenas, made by [`Arena::diff`] and applied by
+/// [`Arena::apply_patch`].
+///
+/// With the `serde` feature, patches can be serialized, so they can be sent to
+/// another process that holds a copy of the arena.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ArenaPatch<T> {
+    next_uid: u64,
+    len: usize,
+    slot_count: usize,
+    removed: Vec<Key>,
+    changed: Vec<(Key, T)>,
+    added: Vec<(Key, T)>,
+    // the slot index of the value at each position where it differs from the
+    // base arena's
+    moved: Vec<(usize, usize)>,
+    first_free: Option<usize>,
+    // the next free slot of each free slot whose link isn't the same in the
+    // base arena
+    free_links: Vec<(usize, Option<usize>)>,
+    // the IDs that are reserved, but weren't in the base arena, and vice versa
+    reserved: Vec<Key>,
+    unreserved: Vec<Key>,
+    // like IDs, serialized patches don't hold the arena's UUID
+    #[cfg(feature = "uuid")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    uuid: uuid::Uuid,
+}
+
+impl<T> ArenaPatch<T> {
+    /// Returns the IDs of the entries that the patch removes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let old = Arena::from(['A', 'B']);
+    /// let mut new = old.clone();
+    /// let a = new.id_at(0).unwrap();
+    /// new.remove(a);
+    ///
+    /// assert!(old.diff(&new).removed().eq([a]));
+    /// ```
+    pub fn removed(&self) -> impl Iterator<Item = ArenaId<T>> + '_ {
+        self.removed.iter().map(|&key| self.id(key))
+    }
+
+    /// Returns the IDs of the entries whose values the patch changes, with their
+    /// new values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let old = Arena::from(['A', 'B']);
+    /// let mut new = old.clone();
+    /// let b = new.id_at(1).unwrap();
+    /// new[b] = 'Z';
+    ///
+    /// assert!(old.diff(&new).changed().eq([(b, &'Z')]));
+    /// ```
+    pub fn changed(&self) -> impl Iterator<Item = (ArenaId<T>, &T)> + '_ {
+        self.changed.iter().map(|(key, val)| (self.id(*key), val))
+    }
+
+    /// Returns the IDs of the entries that the patch adds, with their values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let old = Arena::from(['A']);
+    /// let mut new = old.clone();
+    /// let b = new.insert('B');
+    ///
+    /// assert!(old.diff(&new).added().eq([(b, &'B')]));
+    /// ```
+    pub fn added(&self) -> impl Iterator<Item = (ArenaId<T>, &T)> + '_ {
+        self.added.iter().map(|(key, val)| (self.id(*key), val))
+    }
+
+    fn id(&self, key: Key) -> ArenaId<T> {
+        ArenaId {
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+            uid: key.uid,
+            idx: key.idx,
+            _ty: PhantomData,
+        }
+    }
+}
+
+// The changes to an arena's slot table that applying a patch makes.
+struct Plan {
+    // the new state of every slot whose state changes
+    states: HashMap<usize, State>,
+    // the old position of each value that stays in the arena, but moves
+    moved_from: HashMap<usize, usize>,
+    // the positions of added entries that take the place of a removed entry in
+    // the same slot, so the slot's position doesn't change
+    in_place: Vec<(usize, usize)>,
+    reserved: usize,
+}
+// An entry's slot index and uid, which identify it across arenas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Key {
+    idx: usize,
+    uid: u64,
+}
+
+impl Key {
+    fn of<T>(id: ArenaId<T>) -> Self {
+        Self {
+            idx: id.idx,
+            uid: id.uid,
+        }
+    }
+}
+
+/// An error returned by [`Arena::apply_patch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// The patch removes or changes an entry that isn't in the arena, or adds
+    /// one to a slot that is in use, so it was made from a different arena.
+    BaseMismatch,
+    /// The patch doesn't describe a valid arena.
+    Invalid,
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BaseMismatch => f.write_str("patch was made from a different arena"),
+            Self::Invalid => f.write_str("patch doesn't describe a valid arena"),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
@@ -0,0 +1,541 @@
+//! An arena split into separately locked shards, for inserting and removing
+//! values from many threads at once.
+
+use crate::{Arena, ArenaId};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+#[cfg(loom)]
+use loom::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex, MutexGuard,
+};
+#[cfg(not(loom))]
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex, MutexGuard,
+};
+
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
+
+/// An arena split into shards, each of which is an [`Arena`] behind its own
+/// lock, so that values can be inserted, removed and accessed from many
+/// threads at once through a shared reference.
+///
+/// Each insertion goes to the next shard that isn't locked, and the index of
+/// the shard is stored in the returned ID, so accessing the value only locks
+/// that shard. Since the values are spread between shards, they are not kept
+/// in any particular order.
+///
+/// # Locking
+///
+/// A [`ShardRef`] or [`ShardRefMut`] keeps its shard locked, so calling any
+/// method that locks the same shard from the thread holding it deadlocks. This
+/// includes [`insert`](ShardedArena::insert) when every other shard is locked
+/// too, since it then waits for a shard that may be the held one.
+///
+/// If a thread panics while holding a shard's lock, such as in the function
+/// passed to [`par_for_each_mut`](ShardedArena::par_for_each_mut) or while
+/// holding a [`ShardRefMut`], its values may have been left part way through
+/// being modified. Like a [`Mutex`](std::sync::Mutex), the shard is then
+/// poisoned, and any later call that locks it panics.
+///
+/// # Examples
+///
+/// ```
+/// # use arena::ShardedArena;
+/// let arena = ShardedArena::with_shards(4);
+/// let arena = &arena;
+///
+/// let ids: Vec<_> = std::thread::scope(|s| {
+///     let workers: Vec<_> = (0..8)
+///         .map(|i| s.spawn(move || arena.insert(i)))
+///         .collect();
+///     workers.into_iter().map(|w| w.join().unwrap()).collect()
+/// });
+///
+/// assert_eq!(arena.len(), 8);
+/// assert_eq!(*arena.get(ids[3]).unwrap(), 3);
+/// assert_eq!(arena.remove(ids[3]), Some(3));
+/// ```
+pub struct ShardedArena<T> {
+    shards: Box<[Mutex<Arena<T>>]>,
+    // where the next insertion starts looking for an unlocked shard
+    next_shard: AtomicUsize,
+    #[cfg(feature = "uuid")]
+    uuid: Uuid,
+}
+
+impl<T> ShardedArena<T> {
+    /// Creates a new, empty arena with four shards for each of the machine's
+    /// available threads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// let arena = ShardedArena::<char>::new();
+    /// assert!(arena.shard_count() >= 4);
+    /// ```
+    pub fn new() -> Self {
+        let threads = std::thread::available_parallelism().map_or(1, usize::from);
+        Self::with_shards(threads * 4)
+    }
+
+    /// Creates a new, empty arena with the number of shards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// let arena = ShardedArena::<char>::with_shards(16);
+    /// assert_eq!(arena.shard_count(), 16);
+    /// ```
+    pub fn with_shards(shards: usize) -> Self {
+        assert!(shards > 0, "a sharded arena needs at least one shard");
+        Self {
+            shards: (0..shards).map(|_| Mutex::new(Arena::new())).collect(),
+            next_shard: AtomicUsize::new(0),
+            #[cfg(feature = "uuid")]
+            uuid: Uuid::new_v4(),
+        }
+    }
+
+    /// Returns the number of shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// let arena = ShardedArena::<char>::with_shards(2);
+    /// assert_eq!(arena.shard_count(), 2);
+    /// ```
+    #[inline]
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the number of values in the arena.
+    ///
+    /// The shards are counted one at a time, so if other threads are inserting
+    /// or removing values, the count may not match the arena at any one time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// let arena = ShardedArena::with_shards(2);
+    /// arena.insert('A');
+    /// arena.insert('B');
+    ///
+    /// assert_eq!(arena.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        (0..self.shards.len()).map(|i| self.lock(i).len()).sum()
+    }
+
+    /// Returns `true` if the arena contains no values.
+    ///
+    /// Like [`len`](ShardedArena::len), this checks the shards one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// let arena = ShardedArena::<char>::with_shards(2);
+    /// assert!(arena.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        (0..self.shards.len()).all(|i| self.lock(i).is_empty())
+    }
+
+    /// Adds a value to the arena, returning its ID.
+    ///
+    /// This takes the first shard that isn't locked, or if they all are, waits
+    /// for one of them. If the current thread holds a [`ShardRef`] or
+    /// [`ShardRefMut`], that may be the one it waits for, which deadlocks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// let arena = ShardedArena::with_shards(2);
+    /// let a = arena.insert('A');
+    ///
+    /// assert_eq!(*arena.get(a).unwrap(), 'A');
+    /// ```
+    pub fn insert(&self, value: T) -> ArenaId<T> {
+        let count = self.shards.len();
+        let start = self.next_shard.fetch_add(1, Ordering::Relaxed) % count;
+
+        // take the first shard that isn't locked, or wait for the first one
+        let (shard, mut arena) = (0..count)
+            .map(|i| (start + i) % count)
+            .find_map(|shard| Some((shard, self.shards[shard].try_lock().ok()?)))
+            .unwrap_or_else(|| (start, self.lock(start)));
+        let id = arena.insert(value);
+        self.outer_id(shard, id)
+    }
+
+    /// Removes the value assigned to the ID, returning it, or `None` if the ID
+    /// isn't assigned to a value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// let arena = ShardedArena::with_shards(2);
+    /// let a = arena.insert('A');
+    ///
+    /// assert_eq!(arena.remove(a), Some('A'));
+    /// assert_eq!(arena.remove(a), None);
+    /// ```
+    pub fn remove(&self, id: ArenaId<T>) -> Option<T> {
+        let (mut arena, id) = self.lock_for(id)?;
+        arena.remove(id)
+    }
+
+    /// Returns `true` if the ID is assigned to a value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// let arena = ShardedArena::with_shards(2);
+    /// let a = arena.insert('A');
+    /// assert!(arena.contains(a));
+    ///
+    /// arena.remove(a);
+    /// assert!(!arena.contains(a));
+    /// ```
+    pub fn contains(&self, id: ArenaId<T>) -> bool {
+        self.lock_for(id)
+            .is_some_and(|(arena, id)| arena.contains(id))
+    }
+
+    /// Returns a reference to the value assigned to the ID, or `None` if it
+    /// isn't assigned to one. The value's shard stays locked until the
+    /// reference is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// let arena = ShardedArena::with_shards(2);
+    /// let a = arena.insert('A');
+    ///
+    /// assert_eq!(*arena.get(a).unwrap(), 'A');
+    /// ```
+    pub fn get(&self, id: ArenaId<T>) -> Option<ShardRef<'_, T>> {
+        let (arena, id) = self.lock_for(id)?;
+        arena.contains(id).then_some(ShardRef { arena, id })
+    }
+
+    /// Returns a mutable reference to the value assigned to the ID, or `None`
+    /// if it isn't assigned to one. The value's shard stays locked until the
+    /// reference is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// let arena = ShardedArena::with_shards(2);
+    /// let a = arena.insert('A');
+    ///
+    /// *arena.get_mut(a).unwrap() = 'Z';
+    /// assert_eq!(*arena.get(a).unwrap(), 'Z');
+    /// ```
+    pub fn get_mut(&self, id: ArenaId<T>) -> Option<ShardRefMut<'_, T>> {
+        let (arena, id) = self.lock_for(id)?;
+        arena.contains(id).then_some(ShardRefMut { arena, id })
+    }
+
+    /// Calls the function on every value and its ID, processing the shards in
+    /// parallel on up to one thread for each of the machine's available
+    /// threads. Each shard is locked while its values are being processed.
+    ///
+    /// The threads are spawned for each call and joined before it returns. To
+    /// make several passes over the values, such as every system of a frame,
+    /// without spawning the threads for each of them, make them all in one
+    /// call to [`par_for_each_shard`](ShardedArena::par_for_each_shard).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    ///
+    /// let arena = ShardedArena::with_shards(4);
+    /// for i in 1..=10 {
+    ///     arena.insert(i);
+    /// }
+    ///
+    /// let sum = AtomicU32::new(0);
+    /// arena.par_for_each(|_, val| {
+    ///     sum.fetch_add(*val, Ordering::Relaxed);
+    /// });
+    /// assert_eq!(sum.into_inner(), 55);
+    /// ```
+    pub fn par_for_each<F>(&self, f: F)
+    where
+        F: Fn(ArenaId<T>, &T) + Sync,
+        T: Send,
+    {
+        self.par_shards(|shard, arena| {
+            for (id, val) in arena.pairs() {
+                f(self.outer_id(shard, id), val);
+            }
+        });
+    }
+
+    /// Calls the function on every value and its ID, with mutable access to
+    /// the value, processing the shards in parallel like
+    /// [`par_for_each`](ShardedArena::par_for_each). Each shard is locked while
+    /// its values are being processed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// let arena = ShardedArena::with_shards(4);
+    /// let a = arena.insert(1);
+    /// let b = arena.insert(2);
+    ///
+    /// arena.par_for_each_mut(|_, val| *val *= 10);
+    /// assert_eq!(*arena.get(a).unwrap(), 10);
+    /// assert_eq!(*arena.get(b).unwrap(), 20);
+    /// ```
+    pub fn par_for_each_mut<F>(&self, f: F)
+    where
+        F: Fn(ArenaId<T>, &mut T) + Sync,
+        T: Send,
+    {
+        self.par_shards(|shard, arena| {
+            for (id, val) in arena.pairs_mut() {
+                f(self.outer_id(shard, id), val);
+            }
+        });
+    }
+
+    /// Calls the function on every shard, processing the shards in parallel
+    /// like [`par_for_each`](ShardedArena::par_for_each). Each shard is locked
+    /// while the function runs on it.
+    ///
+    /// This spawns the threads once for every pass the function makes over the
+    /// shard's values, where calling [`par_for_each_mut`] for each pass would
+    /// spawn them again every time.
+    ///
+    /// [`par_for_each_mut`]: ShardedArena::par_for_each_mut
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::ShardedArena;
+    /// let arena = ShardedArena::with_shards(4);
+    /// let a = arena.insert(1);
+    /// let b = arena.insert(2);
+    ///
+    /// arena.par_for_each_shard(|mut shard| {
+    ///     for (_, val) in shard.pairs_mut() {
+    ///         *val *= 10;
+    ///     }
+    ///     for (_, val) in shard.pairs_mut() {
+    ///         *val += 1;
+    ///     }
+    /// });
+    /// assert_eq!(*arena.get(a).unwrap(), 11);
+    /// assert_eq!(*arena.get(b).unwrap(), 21);
+    /// ```
+    pub fn par_for_each_shard<F>(&self, f: F)
+    where
+        F: Fn(Shard<'_, T>) + Sync,
+        T: Send,
+    {
+        self.par_shards(|shard, arena| {
+            f(Shard {
+                sharded: self,
+                shard,
+                arena,
+            })
+        });
+    }
+
+    fn par_shards<F>(&self, f: F)
+    where
+        F: Fn(usize, &mut Arena<T>) + Sync,
+        T: Send,
+    {
+        // each worker takes the next shard until there are none left, with the
+        // calling thread as one of them
+        let count = self.shards.len();
+        let threads = std::thread::available_parallelism().map_or(1, usize::from);
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        let work = || loop {
+            let shard = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            if shard >= count {
+                break;
+            }
+            f(shard, &mut self.lock(shard));
+        };
+        std::thread::scope(|s| {
+            for _ in 1..threads.min(count) {
+                s.spawn(work);
+            }
+            work();
+        });
+    }
+
+    fn lock(&self, shard: usize) -> MutexGuard<'_, Arena<T>> {
+        self.shards[shard]
+            .lock()
+            .expect("a thread panicked while holding the shard's lock")
+    }
+
+    // locks the ID's shard, returning it with the ID it has within the shard
+    fn lock_for(&self, id: ArenaId<T>) -> Option<(MutexGuard<'_, Arena<T>>, ArenaId<T>)> {
+        #[cfg(feature = "uuid")]
+        if id.uuid != self.uuid {
+            return None;
+        }
+        let count = self.shards.len();
+        let arena = self.lock(id.idx % count);
+        let id = ArenaId {
+            #[cfg(feature = "uuid")]
+            uuid: arena.uuid,
+            uid: id.uid,
+            idx: id.idx / count,
+            _ty: PhantomData,
+        };
+        Some((arena, id))
+    }
+
+    // the ID of a value in the shard, as given out by the sharded arena
+    fn outer_id(&self, shard: usize, id: ArenaId<T>) -> ArenaId<T> {
+        ArenaId {
+            #[cfg(feature = "uuid")]
+            uuid: self.uuid,
+            uid: id.uid,
+            idx: id.idx * self.shards.len() + shard,
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for ShardedArena<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> std::fmt::Debug for ShardedArena<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShardedArena")
+            .field("shards", &self.shards.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A reference to a value in a [`ShardedArena`], which keeps the value's shard
+/// locked until it is dropped.
+///
+/// This struct is created by the [`get`](ShardedArena::get) method on
+/// [`ShardedArena`].
+pub struct ShardRef<'a, T> {
+    arena: MutexGuard<'a, Arena<T>>,
+    id: ArenaId<T>,
+}
+
+impl<'a, T> Deref for ShardRef<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.arena[self.id]
+    }
+}
+
+/// A mutable reference to a value in a [`ShardedArena`], which keeps the
+/// value's shard locked until it is dropped.
+///
+/// This struct is created by the [`get_mut`](ShardedArena::get_mut) method on
+/// [`ShardedArena`].
+pub struct ShardRefMut<'a, T> {
+    arena: MutexGuard<'a, Arena<T>>,
+    id: ArenaId<T>,
+}
+
+impl<'a, T> Deref for ShardRefMut<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.arena[self.id]
+    }
+}
+
+impl<'a, T> DerefMut for ShardRefMut<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.arena[self.id]
+    }
+}
+
+/// One locked shard of a [`ShardedArena`], whose values are accessed with the
+/// IDs given out by the sharded arena.
+///
+/// This struct is created by the
+/// [`par_for_each_shard`](ShardedArena::par_for_each_shard) method on
+/// [`ShardedArena`].
+pub struct Shard<'a, T> {
+    sharded: &'a ShardedArena<T>,
+    shard: usize,
+    arena: &'a mut Arena<T>,
+}
+
+impl<'a, T> Shard<'a, T> {
+    /// Returns the number of values in the shard.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Returns `true` if the shard contains no values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+
+    /// Returns an iterator over the shard's values and their IDs.
+    pub fn pairs(&self) -> impl Iterator<Item = (ArenaId<T>, &T)> + '_ {
+        let sharded = self.sharded;
+        let shard = self.shard;
+        self.arena
+            .pairs()
+            .map(move |(id, val)| (sharded.outer_id(shard, id), val))
+    }
+
+    /// Returns an iterator over the shard's values and their IDs, with mutable
+    /// access to the values.
+    pub fn pairs_mut(&mut self) -> impl Iterator<Item = (ArenaId<T>, &mut T)> + '_ {
+        let sharded = self.sharded;
+        let shard = self.shard;
+        self.arena
+            .pairs_mut()
+            .map(move |(id, val)| (sharded.outer_id(shard, id), val))
+    }
+}
+
+impl<T> std::fmt::Debug for Shard<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shard")
+            .field("shard", &self.shard)
+            .field("len", &self.arena.len())
+            .finish()
+    }
+}
@@ -0,0 +1,567 @@
+//! A compact, versioned binary format for saving and loading arenas.
+//!
+//! Unlike the `serde` implementation, snapshots store the arena's entire slot
+//! table, including its free slots, so a loaded arena behaves exactly like the
+//! one that was saved: every ID stays valid and new values will be assigned the
+//! same IDs they would have been assigned in the original arena.
+//!
+//! IDs held by the values are stored without a UUID, so with the `uuid`
+//! feature they are loaded with the UUID of the arena being read. IDs into
+//! other arenas don't keep their own UUID, and have to be fixed up separately.
+//!
+//! # Format
+//!
+//! All integers are little-endian.
+//!
+//! | Field       | Type       | Notes                                         |
+//! |-------------|------------|-----------------------------------------------|
+//! | magic       | `[u8; 4]`  | always `b"ARNA"`                              |
+//! | version     | `u16`      | see [`SNAPSHOT_VERSION`]                      |
+//! | uuid        | `[u8; 16]` | the arena's UUID, or all zeros if it has none |
+//! | next_uid    | `u64`      |                                               |
+//! | first_free  | `u64`      | `u64::MAX` if there are no free slots         |
+//! | slot count  | `u64`      |                                               |
+//! | value count | `u64`      |                                               |
+//! | slots       | ...        | one entry per slot, see below                 |
+//! | values      | ...        | one [`SnapshotValue`] per value, in order     |
+//!
+//! Each slot starts with a tag byte. A free slot (`0`) is followed by the
+//! index of the next free slot (`u64`, `u64::MAX` if none), a used slot (`1`)
+//! is followed by its uid (`u64`) and the index of its value (`u64`), and a
+//! reserved slot (`2`) is followed by its uid (`u64`).
+//!
+//! # Versions
+//!
+//! - `1`: the initial format.
+//! - `2`: adds reserved slots.
+
+use crate::{Arena, ArenaId, Slot, State};
+use std::fmt::{Display, Formatter};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+#[cfg(feature = "uuid")]
+use std::cell::Cell;
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
+
+/// The version of the snapshot format written by [`Arena::write_snapshot`].
+///
+/// [`Arena::read_snapshot`] can read snapshots of this version or any older one.
+pub const SNAPSHOT_VERSION: u16 = 2;
+
+const MAGIC: [u8; 4] = *b"ARNA";
+const NONE: u64 = u64::MAX;
+
+const TAG_FREE: u8 = 0;
+const TAG_USED: u8 = 1;
+const TAG_RESERVED: u8 = 2;
+
+// limits how much memory is reserved up front for lengths read from a snapshot,
+// so a corrupted length fails with `Truncated` instead of a huge allocation
+const MAX_PREALLOC: usize = 4096;
+
+#[cfg(feature = "uuid")]
+thread_local! {
+    // the UUID given to the IDs read by `ArenaId::read_value`, which is the
+    // UUID of the arena whose values are being read
+    static READ_UUID: Cell<Uuid> = const { Cell::new(Uuid::nil()) };
+}
+
+// sets the UUID given to the IDs read on this thread until it's dropped, and
+// then restores the previous one, so that reads can be nested
+#[cfg(feature = "uuid")]
+struct ReadUuidGuard(Uuid);
+
+#[cfg(feature = "uuid")]
+impl ReadUuidGuard {
+    fn set(uuid: Uuid) -> Self {
+        Self(READ_UUID.with(|cell| cell.replace(uuid)))
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl Drop for ReadUuidGuard {
+    fn drop(&mut self) {
+        READ_UUID.with(|cell| cell.set(self.0));
+    }
+}
+
+impl<T: SnapshotValue> Arena<T> {
+    /// Writes a binary snapshot of the arena to the writer.
+    ///
+    /// The snapshot is written with many small writes, so if the writer is
+    /// something like a [`File`](std::fs::File) it should be wrapped in a
+    /// [`BufWriter`](std::io::BufWriter).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert(String::from("A"));
+    /// let b = arena.insert(String::from("B"));
+    /// arena.remove(a);
+    ///
+    /// let mut bytes = Vec::new();
+    /// arena.write_snapshot(&mut bytes).unwrap();
+    ///
+    /// let mut loaded: Arena<String> = Arena::read_snapshot(bytes.as_slice()).unwrap();
+    /// assert_eq!(loaded.get(a), None);
+    /// assert_eq!(loaded.get(b).map(String::as_str), Some("B"));
+    ///
+    /// // the free slot was kept, so new values get the same IDs in both arenas
+    /// assert_eq!(loaded.insert(String::from("C")), arena.insert(String::from("C")));
+    /// ```
+    pub fn write_snapshot<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let w = &mut writer;
+        w.write_all(&MAGIC)?;
+        w.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        #[cfg(feature = "uuid")]
+        w.write_all(self.uuid.as_bytes())?;
+        #[cfg(not(feature = "uuid"))]
+        w.write_all(&[0; 16])?;
+        self.next_uid.write_value(w)?;
+        write_index(w, self.first_free)?;
+        self.slots.len().write_value(w)?;
+        self.values.len().write_value(w)?;
+
+        for slot in &self.slots {
+            match &slot.state {
+                State::Free { next_free } => {
+                    TAG_FREE.write_value(w)?;
+                    write_index(w, *next_free)?;
+                }
+                State::Used { uid, value } => {
+                    TAG_USED.write_value(w)?;
+                    uid.write_value(w)?;
+                    value.write_value(w)?;
+                }
+                State::Reserved { uid } => {
+                    TAG_RESERVED.write_value(w)?;
+                    uid.write_value(w)?;
+                }
+            }
+        }
+
+        for value in &self.values {
+            value.write_value(w)?;
+        }
+
+        w.flush()
+    }
+
+    /// Reads an arena from a binary snapshot written by [`write_snapshot`](Arena::write_snapshot).
+    ///
+    /// Snapshots written by older versions of this crate can be read as well. If
+    /// the snapshot ends early, or its contents don't describe a valid arena, an
+    /// error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::{Arena, SnapshotError};
+    /// let arena = Arena::from([1u32, 2, 3]);
+    ///
+    /// let mut bytes = Vec::new();
+    /// arena.write_snapshot(&mut bytes).unwrap();
+    ///
+    /// let loaded: Arena<u32> = Arena::read_snapshot(bytes.as_slice()).unwrap();
+    /// assert_eq!(loaded.as_slice(), &[1, 2, 3]);
+    ///
+    /// let res = Arena::<u32>::read_snapshot(&bytes[..bytes.len() - 1]);
+    /// assert!(matches!(res, Err(SnapshotError::Truncated)));
+    ///
+    /// let res = Arena::<u32>::read_snapshot(&b"not a snapshot"[..]);
+    /// assert!(matches!(res, Err(SnapshotError::NotASnapshot)));
+    /// ```
+    pub fn read_snapshot<R: Read>(mut reader: R) -> Result<Self, SnapshotError> {
+        let r = &mut reader;
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+
+        // when the format changes, bump `SNAPSHOT_VERSION` and keep a branch
+        // here for each older version so existing snapshots can still be read
+        match u16::read_value(r)? {
+            version @ 1..=2 => Self::read_v1(r, version),
+            version => Err(SnapshotError::UnsupportedVersion(version)),
+        }
+    }
+
+    // reads version 1 and the versions that only extended it
+    fn read_v1<R: Read>(r: &mut R, version: u16) -> Result<Self, SnapshotError> {
+        #[cfg_attr(not(feature = "uuid"), allow(unused_variables))]
+        let mut uuid = [0; 16];
+        r.read_exact(&mut uuid)?;
+        let next_uid = u64::read_value(r)?;
+        let first_free = read_index(r)?;
+        let slot_count = usize::read_value(r)?;
+        let value_count = usize::read_value(r)?;
+        if value_count > slot_count {
+            return Err(SnapshotError::corrupt("more values than slots"));
+        }
+
+        let mut slots = Vec::with_capacity(slot_count.min(MAX_PREALLOC));
+        for _ in 0..slot_count {
+            let state = match u8::read_value(r)? {
+                TAG_FREE => State::Free {
+                    next_free: read_index(r)?,
+                },
+                TAG_USED => State::Used {
+                    uid: u64::read_value(r)?,
+                    value: usize::read_value(r)?,
+                },
+                TAG_RESERVED if version >= 2 => State::Reserved {
+                    uid: u64::read_value(r)?,
+                },
+                _ => return Err(SnapshotError::corrupt("unknown slot tag")),
+            };
+            slots.push(Slot {
+                value_slot: 0,
+                state,
+            });
+        }
+
+        // the arena is created first so IDs in the values can be given its UUID
+        let mut arena = Self::with_capacity(0);
+        #[cfg(feature = "uuid")]
+        if uuid != [0; 16] {
+            arena.uuid = Uuid::from_bytes(uuid);
+        }
+
+        let mut values = Vec::with_capacity(value_count.min(MAX_PREALLOC));
+        {
+            #[cfg(feature = "uuid")]
+            let _guard = ReadUuidGuard::set(arena.uuid);
+            for _ in 0..value_count {
+                values.push(T::read_value(r)?);
+            }
+        }
+
+        arena.values = values;
+        arena.slots = slots;
+        arena.next_uid = next_uid;
+        arena.first_free = first_free;
+        arena.reserved = arena
+            .slots
+            .iter()
+            .filter(|slot| matches!(slot.state, State::Reserved { .. }))
+            .count();
+        arena.link_value_slots()?;
+        Ok(arena)
+    }
+
+    // rebuilds the value -> slot mapping from the slot states, then checks that
+    // the slot table they describe is consistent
+    fn link_value_slots(&mut self) -> Result<(), SnapshotError> {
+        let len = self.values.len();
+        for idx in 0..self.slots.len() {
+            if let State::Used { value, .. } = self.slots[idx].state {
+                if value < len {
+                    self.slots[value].value_slot = idx;
+                }
+            }
+        }
+        self.check_invariants()
+            .map_err(|err| SnapshotError::Corrupt(err.to_string()))
+    }
+}
+
+fn write_index<W: Write>(w: &mut W, index: Option<usize>) -> io::Result<()> {
+    index.map_or(NONE, |i| i as u64).write_value(w)
+}
+
+fn read_index<R: Read>(r: &mut R) -> Result<Option<usize>, SnapshotError> {
+    match u64::read_value(r)? {
+        NONE => Ok(None),
+        i => usize::try_from(i)
+            .map(Some)
+            .map_err(|_| SnapshotError::corrupt("slot index out of range")),
+    }
+}
+
+/// An error that occurred while reading an arena snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The underlying reader returned an error.
+    Io(io::Error),
+    /// The snapshot ended before all of its data was read.
+    Truncated,
+    /// The data does not start with the snapshot header.
+    NotASnapshot,
+    /// The snapshot was written with a newer, unknown version of the format.
+    UnsupportedVersion(u16),
+    /// The snapshot's contents don't describe a valid arena.
+    Corrupt(String),
+}
+
+impl SnapshotError {
+    fn corrupt(msg: &str) -> Self {
+        Self::Corrupt(msg.to_string())
+    }
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read snapshot: {err}"),
+            Self::Truncated => f.write_str("snapshot is truncated"),
+            Self::NotASnapshot => f.write_str("data is not an arena snapshot"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported snapshot version {v}"),
+            Self::Corrupt(msg) => write!(f, "snapshot is corrupt: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => Self::Truncated,
+            io::ErrorKind::InvalidData => Self::Corrupt(err.to_string()),
+            _ => Self::Io(err),
+        }
+    }
+}
+
+/// A value that can be stored in an arena snapshot.
+///
+/// This is implemented for primitive types, strings, and common containers of
+/// them. Implementations for your own types can usually just write and read
+/// each of their fields in turn:
+///
+/// ```
+/// # use arena::SnapshotValue;
+/// # use std::io::{self, Read, Write};
+/// struct Particle {
+///     pos: (f32, f32),
+///     life: u32,
+/// }
+///
+/// impl SnapshotValue for Particle {
+///     fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+///         self.pos.write_value(w)?;
+///         self.life.write_value(w)
+///     }
+///
+///     fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+///         Ok(Self {
+///             pos: SnapshotValue::read_value(r)?,
+///             life: SnapshotValue::read_value(r)?,
+///         })
+///     }
+/// }
+/// ```
+///
+/// Decoding errors should be reported with [`io::ErrorKind::InvalidData`], which
+/// [`Arena::read_snapshot`] reports as [`SnapshotError::Corrupt`].
+pub trait SnapshotValue: Sized {
+    /// Writes the value to the writer.
+    fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()>;
+
+    /// Reads a value previously written with [`write_value`](SnapshotValue::write_value).
+    fn read_value<R: Read>(r: &mut R) -> io::Result<Self>;
+}
+
+macro_rules! impl_snapshot_num {
+    ($($ty:ty),*) => {
+        $(
+            impl SnapshotValue for $ty {
+                #[inline]
+                fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+                    w.write_all(&self.to_le_bytes())
+                }
+
+                #[inline]
+                fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+                    let mut bytes = [0; std::mem::size_of::<$ty>()];
+                    r.read_exact(&mut bytes)?;
+                    Ok(<$ty>::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_snapshot_num!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// sizes are always stored as 64 bits so snapshots are portable across platforms
+impl SnapshotValue for usize {
+    #[inline]
+    fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u64).write_value(w)
+    }
+
+    #[inline]
+    fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+        usize::try_from(u64::read_value(r)?).map_err(|_| invalid_data("usize out of range"))
+    }
+}
+
+impl SnapshotValue for isize {
+    #[inline]
+    fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (*self as i64).write_value(w)
+    }
+
+    #[inline]
+    fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+        isize::try_from(i64::read_value(r)?).map_err(|_| invalid_data("isize out of range"))
+    }
+}
+
+impl SnapshotValue for bool {
+    #[inline]
+    fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u8).write_value(w)
+    }
+
+    #[inline]
+    fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+        match u8::read_value(r)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data("invalid bool")),
+        }
+    }
+}
+
+impl SnapshotValue for char {
+    #[inline]
+    fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u32).write_value(w)
+    }
+
+    #[inline]
+    fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+        char::from_u32(u32::read_value(r)?).ok_or_else(|| invalid_data("invalid char"))
+    }
+}
+
+impl SnapshotValue for String {
+    fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.len().write_value(w)?;
+        w.write_all(self.as_bytes())
+    }
+
+    fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+        let len = usize::read_value(r)?;
+        let mut bytes = Vec::with_capacity(len.min(MAX_PREALLOC));
+        r.take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        String::from_utf8(bytes).map_err(|_| invalid_data("invalid UTF-8 in string"))
+    }
+}
+
+impl<V: SnapshotValue> SnapshotValue for Vec<V> {
+    fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.len().write_value(w)?;
+        for val in self {
+            val.write_value(w)?;
+        }
+        Ok(())
+    }
+
+    fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+        let len = usize::read_value(r)?;
+        let mut vec = Vec::with_capacity(len.min(MAX_PREALLOC));
+        for _ in 0..len {
+            vec.push(V::read_value(r)?);
+        }
+        Ok(vec)
+    }
+}
+
+impl<V: SnapshotValue> SnapshotValue for Option<V> {
+    fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match self {
+            Some(val) => {
+                true.write_value(w)?;
+                val.write_value(w)
+            }
+            None => false.write_value(w),
+        }
+    }
+
+    fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+        match bool::read_value(r)? {
+            true => Ok(Some(V::read_value(r)?)),
+            false => Ok(None),
+        }
+    }
+}
+
+impl<V: SnapshotValue> SnapshotValue for Box<V> {
+    #[inline]
+    fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (**self).write_value(w)
+    }
+
+    #[inline]
+    fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+        V::read_value(r).map(Box::new)
+    }
+}
+
+macro_rules! impl_snapshot_tuple {
+    ($($name:ident)+) => {
+        impl<$($name: SnapshotValue),+> SnapshotValue for ($($name,)+) {
+            #[inline]
+            #[allow(non_snake_case)]
+            fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+                let ($($name,)+) = self;
+                $($name.write_value(w)?;)+
+                Ok(())
+            }
+
+            #[inline]
+            fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+                Ok(($($name::read_value(r)?,)+))
+            }
+        }
+    };
+}
+
+impl_snapshot_tuple!(A);
+impl_snapshot_tuple!(A B);
+impl_snapshot_tuple!(A B C);
+impl_snapshot_tuple!(A B C D);
+
+// Like the `serde` implementation, IDs don't store the arena's UUID. With the
+// `uuid` feature, IDs read as part of a snapshot's values are given the UUID of
+// the arena being read, and other IDs a nil one.
+impl<T> SnapshotValue for ArenaId<T> {
+    #[inline]
+    fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.uid.write_value(w)?;
+        self.idx.write_value(w)
+    }
+
+    #[inline]
+    fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+        Ok(ArenaId {
+            #[cfg(feature = "uuid")]
+            uuid: READ_UUID.with(Cell::get),
+            uid: u64::read_value(r)?,
+            idx: usize::read_value(r)?,
+            _ty: PhantomData,
+        })
+    }
+}
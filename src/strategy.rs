@@ -0,0 +1,76 @@
+//! [`proptest`](mod@proptest) strategies for generating arenas, enabled with the `proptest` feature.
+//!
+//! Arenas are generated by replaying a random sequence of `insert`, `remove`,
+//! `swap` and `sort_by` calls on an empty arena, so they can contain free and
+//! reused slots, and be paired with IDs that have gone stale.
+//!
+//! # Examples
+//!
+//! ```
+//! use arena::strategy;
+//! use proptest::prelude::*;
+//!
+//! proptest!(|((arena, ids) in strategy::arena_with_ids(any::<u8>(), 32))| {
+//!     for id in ids {
+//!         // every ID is either live, or stale and resolves to nothing
+//!         assert_eq!(arena.contains(id), arena.index_of(id).is_some());
+//!     }
+//! });
+//! ```
+
+use crate::fuzz::{replay, Op};
+use crate::{Arena, ArenaId};
+use proptest::arbitrary::{any_with, Arbitrary};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use std::fmt::Debug;
+
+/// Returns a strategy that generates arenas by replaying up to `max_ops`
+/// random operations, inserting values generated by `values`.
+pub fn arena<S>(values: S, max_ops: usize) -> impl Strategy<Value = Arena<S::Value>>
+where
+    S: Strategy,
+    S::Value: Clone,
+{
+    ops(values, max_ops).prop_map(|ops| replay(ops).0)
+}
+
+/// Returns a strategy that generates arenas like [`arena`], paired with every ID
+/// issued while generating it. Some of these IDs are live, and the others are
+/// stale IDs of values that were removed.
+pub fn arena_with_ids<S>(
+    values: S,
+    max_ops: usize,
+) -> impl Strategy<Value = (Arena<S::Value>, Vec<ArenaId<S::Value>>)>
+where
+    S: Strategy,
+    S::Value: Clone,
+{
+    ops(values, max_ops).prop_map(replay)
+}
+
+fn ops<S>(values: S, max_ops: usize) -> impl Strategy<Value = Vec<Op<S::Value>>>
+where
+    S: Strategy,
+    S::Value: Clone,
+{
+    let op = prop_oneof![
+        3 => values.prop_map(Op::Insert),
+        1 => any::<usize>().prop_map(Op::Remove),
+        1 => any::<(usize, usize)>().prop_map(|(i, j)| Op::Swap(i, j)),
+        1 => any::<u64>().prop_map(Op::Sort),
+    ];
+    vec(op, 0..=max_ops)
+}
+
+impl<T> Arbitrary for Arena<T>
+where
+    T: Arbitrary + Clone + Debug + 'static,
+{
+    type Parameters = T::Parameters;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(args: Self::Parameters) -> Self::Strategy {
+        arena(any_with::<T>(args), 64).boxed()
+    }
+}
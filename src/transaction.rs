@@ -0,0 +1,541 @@
+//! Transactions that can roll an arena back to the state it was in when they
+//! began.
+//!
+//! While a transaction is open, every edit to the arena's values and slots is
+//! recorded in an undo log, along with whatever is needed to reverse it. All
+//! edits go through the primitives below, so that nothing can be missed.
+
+use crate::{Arena, ArenaId, Change, Slot};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Formatter};
+use std::ops::{Deref, DerefMut, Range};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// gives every undo log a distinct generation, so that a guard can tell whether
+// the arena's log is still the one it was begun in
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+impl<T: Clone> Arena<T> {
+    /// Begins a transaction, returning a guard through which the arena can be
+    /// modified as usual.
+    ///
+    /// If the transaction is [rolled back](Transaction::rollback), or the guard
+    /// is dropped without being [committed](Transaction::commit), the arena is
+    /// restored to exactly the state it was in when the transaction began: the
+    /// same values in the same order, assigned the same IDs, with the same free
+    /// slots, so it will also assign the same IDs to new values.
+    ///
+    /// Transactions can be nested by beginning a new one through the guard.
+    /// Rolling back a nested transaction only undoes the changes made since it
+    /// began, and committing it leaves them to the outer transaction.
+    ///
+    /// Values that are mutably accessed during a transaction are cloned first,
+    /// so that they can be restored.
+    ///
+    /// If the guard of an earlier transaction was leaked, such as with
+    /// [`mem::forget`](std::mem::forget), its changes are kept, and the undo
+    /// log it left behind is discarded when this is called. A nested
+    /// transaction must therefore be begun with
+    /// [`Transaction::begin_transaction`], not by calling this on the arena
+    /// the guard dereferences to. That ends the outer transaction, keeping
+    /// the changes made in it so far, and its guard no longer rolls back
+    /// anything.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.insert('A');
+    /// let b = arena.insert('B');
+    ///
+    /// let mut tx = arena.begin_transaction();
+    /// tx.remove(a);
+    /// tx[b] = 'Z';
+    /// let c = tx.insert('C');
+    /// tx.rollback();
+    ///
+    /// assert_eq!(arena.as_slice(), &['A', 'B']);
+    /// assert_eq!(arena.get(a), Some(&'A'));
+    /// assert_eq!(arena.get(c), None);
+    ///
+    /// let mut tx = arena.begin_transaction();
+    /// tx.remove(a);
+    /// tx.commit();
+    ///
+    /// assert_eq!(arena.as_slice(), &['B']);
+    /// ```
+    pub fn begin_transaction(&mut self) -> Transaction<'_, T> {
+        // no guard can be borrowing the arena, so any log is from a leaked one
+        self.undo = None;
+        self.push_savepoint()
+    }
+
+    fn push_savepoint(&mut self) -> Transaction<'_, T> {
+        let savepoint = Savepoint {
+            edits: self.undo.as_ref().map_or(0, |log| log.edits.len()),
+            counters: Counters::of(self),
+        };
+        let log = self.undo.get_or_insert_with(|| UndoLog {
+            clone: T::clone,
+            generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+            edits: Vec::new(),
+            savepoints: Vec::new(),
+        });
+        log.savepoints.push(savepoint);
+        let depth = log.savepoints.len();
+        let generation = log.generation;
+        Transaction {
+            arena: self,
+            generation,
+            depth,
+            finished: false,
+        }
+    }
+}
+
+impl<T> Arena<T> {
+    // Returns the log of the transaction at the depth, with savepoints of any
+    // leaked transactions nested in it dropped, so their edits become its own.
+    // Returns `None` if the transaction was already ended by beginning another
+    // one directly on the arena, which discarded its log.
+    fn transaction_log(&mut self, depth: usize, generation: u64) -> Option<&mut UndoLog<T>> {
+        let log = self
+            .undo
+            .as_mut()
+            .filter(|log| log.generation == generation && log.savepoints.len() >= depth)?;
+        log.savepoints.truncate(depth);
+        Some(log)
+    }
+
+    // closes the transaction at the depth, undoing its edits if rolling back,
+    // and discarding the log once the outermost one is closed
+    fn end_transaction(&mut self, depth: usize, generation: u64, rollback: bool) {
+        let Some(log) = self.transaction_log(depth, generation) else {
+            return;
+        };
+        let savepoint = log.savepoints.pop().unwrap();
+        let mut log = self.undo.take().unwrap();
+        if rollback {
+            self.revert(log.edits.drain(savepoint.edits..), savepoint.counters);
+        }
+        if !log.savepoints.is_empty() {
+            self.undo = Some(log);
+        }
+    }
+
+    // Undoes the edits, last first, and restores the counters. Returns the
+    // edits that were made in doing so, which will revert it in turn.
+    pub(crate) fn revert<I>(&mut self, edits: I, counters: Counters) -> Vec<Edit<T>>
+    where
+        I: DoubleEndedIterator<Item = Edit<T>>,
+    {
+        let before = self.is_recording_changes().then(|| self.positions());
+
+        let mut modified = Vec::new();
+        let inverse = edits
+            .rev()
+            .map(|edit| match edit {
+                Edit::SlotSet(idx, slot) => {
+                    Edit::SlotSet(idx, std::mem::replace(&mut self.slots[idx], slot))
+                }
+                Edit::SlotPushed => Edit::SlotPopped(self.slots.pop().unwrap()),
+                Edit::SlotPopped(slot) => {
+                    self.slots.push(slot);
+                    Edit::SlotPushed
+                }
+                Edit::SlotsReplaced(slots) => {
+                    Edit::SlotsReplaced(std::mem::replace(&mut self.slots, slots))
+                }
+                Edit::ValuePushed => Edit::ValuePopped(self.values.pop().unwrap()),
+                Edit::ValuePopped(value) => {
+                    self.values.push(value);
+                    Edit::ValuePushed
+                }
+                Edit::ValuesSwapped(i, j) => {
+                    self.values.swap(i, j);
+                    Edit::ValuesSwapped(i, j)
+                }
+                Edit::ValueSet { pos, id, old } => {
+                    modified.push(id);
+                    let old = std::mem::replace(&mut self.values[pos], old);
+                    Edit::ValueSet { pos, id, old }
+                }
+                Edit::ValuesReplaced(values) => {
+                    Edit::ValuesReplaced(std::mem::replace(&mut self.values, values))
+                }
+            })
+            .collect();
+        counters.restore(self);
+
+        if let Some(before) = before {
+            self.log_diff(before, modified);
+        }
+        self.debug_check();
+        inverse
+    }
+
+    pub(crate) fn positions(&self) -> HashMap<ArenaId<T>, usize> {
+        self.ids().enumerate().map(|(pos, id)| (id, pos)).collect()
+    }
+
+    // records the changes between the arena's positions before and after being
+    // replaced wholesale, as the change log would have if they had been made
+    // one at a time
+    pub(crate) fn log_diff(
+        &mut self,
+        before: HashMap<ArenaId<T>, usize>,
+        modified: Vec<ArenaId<T>>,
+    ) {
+        let after = self.positions();
+        let mut len = before.len();
+        for &id in before.keys() {
+            if !after.contains_key(&id) {
+                self.log_change(|_| Change::Removed(id));
+                len -= 1;
+            }
+        }
+
+        let mut inserted = Vec::new();
+        for (pos, id) in self.ids().enumerate() {
+            match before.get(&id) {
+                Some(&from) if from != pos => inserted.push((id, Some(from), pos)),
+                Some(_) => {}
+                None => inserted.push((id, None, pos)),
+            }
+        }
+        for (id, from, to) in inserted {
+            let from = from.unwrap_or_else(|| {
+                self.log_change(|_| Change::Inserted(id));
+                len += 1;
+                len - 1
+            });
+            if from != to {
+                self.log_change(|_| Change::Moved { id, from, to });
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for id in modified {
+            if before.contains_key(&id) && after.contains_key(&id) && seen.insert(id) {
+                self.log_change(|_| Change::Modified(id));
+            }
+        }
+    }
+
+    // The primitives that every edit to the values and slots goes through.
+
+    #[inline]
+    pub(crate) fn slot_mut(&mut self, idx: usize) -> &mut Slot {
+        if let Some(log) = &mut self.undo {
+            log.edits.push(Edit::SlotSet(idx, self.slots[idx].clone()));
+        }
+        &mut self.slots[idx]
+    }
+
+    #[inline]
+    pub(crate) fn slots_push(&mut self, slot: Slot) {
+        if let Some(log) = &mut self.undo {
+            log.edits.push(Edit::SlotPushed);
+        }
+        self.slots.push(slot);
+    }
+
+    #[inline]
+    pub(crate) fn slots_pop(&mut self) -> Option<Slot> {
+        let slot = self.slots.pop()?;
+        if let Some(log) = &mut self.undo {
+            log.edits.push(Edit::SlotPopped(slot.clone()));
+        }
+        Some(slot)
+    }
+
+    pub(crate) fn slots_clear(&mut self) {
+        match &mut self.undo {
+            Some(log) => log
+                .edits
+                .push(Edit::SlotsReplaced(std::mem::take(&mut self.slots))),
+            None => self.slots.clear(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn values_push(&mut self, value: T) {
+        if let Some(log) = &mut self.undo {
+            log.edits.push(Edit::ValuePushed);
+        }
+        self.values.push(value);
+    }
+
+    #[inline]
+    pub(crate) fn values_pop(&mut self) -> Option<T> {
+        let value = self.values.pop()?;
+        if let Some(log) = &mut self.undo {
+            log.edits.push(Edit::ValuePopped((log.clone)(&value)));
+        }
+        Some(value)
+    }
+
+    #[inline]
+    pub(crate) fn values_swap(&mut self, i: usize, j: usize) {
+        if let Some(log) = &mut self.undo {
+            log.edits.push(Edit::ValuesSwapped(i, j));
+        }
+        self.values.swap(i, j);
+    }
+
+    // replaces the value at the position, which belongs to the entry with the ID
+    #[inline]
+    pub(crate) fn value_replace(&mut self, pos: usize, id: ArenaId<T>, value: T) -> T {
+        let old = std::mem::replace(&mut self.values[pos], value);
+        if let Some(log) = &mut self.undo {
+            let old = (log.clone)(&old);
+            log.edits.push(Edit::ValueSet { pos, id, old });
+        }
+        old
+    }
+
+    // the cleared values are kept alive by the log until it is discarded
+    pub(crate) fn values_clear(&mut self) {
+        match &mut self.undo {
+            Some(log) => log
+                .edits
+                .push(Edit::ValuesReplaced(std::mem::take(&mut self.values))),
+            None => self.values.clear(),
+        }
+    }
+
+    // saves the values at the positions before they are mutably accessed
+    pub(crate) fn record_values(&mut self, positions: Range<usize>) {
+        let Some(log) = &self.undo else {
+            return;
+        };
+        let clone = log.clone;
+        let edits: Vec<_> = positions
+            .map(|pos| Edit::ValueSet {
+                pos,
+                id: self.slot_ids().value_id(pos),
+                old: clone(&self.values[pos]),
+            })
+            .collect();
+        if let Some(log) = &mut self.undo {
+            log.edits.extend(edits);
+        }
+    }
+}
+
+/// A guard for a transaction on an arena, created by [`Arena::begin_transaction`].
+///
+/// The arena can be accessed and modified through the guard. If the guard is
+/// dropped without calling [`commit`](Transaction::commit), the transaction
+/// is rolled back.
+pub struct Transaction<'a, T> {
+    arena: &'a mut Arena<T>,
+    // the generation of the log this was begun in
+    generation: u64,
+    // the number of savepoints while this is the innermost transaction
+    depth: usize,
+    finished: bool,
+}
+
+impl<'a, T: Clone> Transaction<'a, T> {
+    /// Begins a transaction nested in this one, returning its guard.
+    ///
+    /// If this transaction was already ended by beginning another one directly
+    /// on the arena, the new transaction isn't nested in anything.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    ///
+    /// let mut outer = arena.begin_transaction();
+    /// outer.insert('A');
+    ///
+    /// let mut inner = outer.begin_transaction();
+    /// inner.insert('B');
+    /// inner.rollback();
+    ///
+    /// outer.commit();
+    /// assert_eq!(arena.as_slice(), &['A']);
+    /// ```
+    pub fn begin_transaction(&mut self) -> Transaction<'_, T> {
+        if self
+            .arena
+            .transaction_log(self.depth, self.generation)
+            .is_none()
+        {
+            // no guard can be borrowing this one, so any log is from a leaked one
+            self.arena.undo = None;
+        }
+        self.arena.push_savepoint()
+    }
+}
+
+impl<'a, T> Transaction<'a, T> {
+    /// Keeps the changes made during the transaction.
+    ///
+    /// If this is a nested transaction, the changes can still be undone by
+    /// rolling back the outer one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::new();
+    ///
+    /// let mut outer = arena.begin_transaction();
+    /// outer.insert('A');
+    ///
+    /// let mut inner = outer.begin_transaction();
+    /// inner.insert('B');
+    /// inner.commit();
+    ///
+    /// assert_eq!(outer.as_slice(), &['A', 'B']);
+    /// outer.rollback();
+    ///
+    /// assert!(arena.is_empty());
+    /// ```
+    pub fn commit(mut self) {
+        self.finished = true;
+        self.arena
+            .end_transaction(self.depth, self.generation, false);
+    }
+
+    /// Undoes the changes made during the transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::from(['C', 'A', 'B']);
+    /// let ids: Vec<_> = arena.ids().collect();
+    ///
+    /// let mut tx = arena.begin_transaction();
+    /// tx.sort();
+    /// tx.pop();
+    /// tx.rollback();
+    ///
+    /// assert_eq!(arena.as_slice(), &['C', 'A', 'B']);
+    /// assert!(arena.ids().eq(ids));
+    /// ```
+    pub fn rollback(mut self) {
+        self.finished = true;
+        self.arena
+            .end_transaction(self.depth, self.generation, true);
+    }
+
+    // Ends the outermost transaction without reverting it, returning its edits
+    // and the counters from when it began, so they can be reverted later.
+    // Returns `None` if the transaction was already ended, so some of its
+    // changes weren't recorded.
+    pub(crate) fn into_edits(mut self) -> Option<(Vec<Edit<T>>, Counters)> {
+        self.finished = true;
+        let savepoint = self
+            .arena
+            .transaction_log(self.depth, self.generation)?
+            .savepoints
+            .pop()
+            .unwrap();
+        let log = self.arena.undo.take().unwrap();
+        debug_assert!(self.depth == 1 && savepoint.edits == 0);
+        Some((log.edits, savepoint.counters))
+    }
+}
+
+impl<'a, T> Deref for Transaction<'a, T> {
+    type Target = Arena<T>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.arena
+    }
+}
+
+impl<'a, T> DerefMut for Transaction<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.arena
+    }
+}
+
+impl<'a, T> Drop for Transaction<'a, T> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.arena
+                .end_transaction(self.depth, self.generation, true);
+        }
+    }
+}
+
+pub(crate) struct UndoLog<T> {
+    // captured when the transaction begins, so that edits can clone values
+    // without every method needing a `T: Clone` bound
+    clone: fn(&T) -> T,
+    generation: u64,
+    edits: Vec<Edit<T>>,
+    savepoints: Vec<Savepoint>,
+}
+
+impl<T> Debug for UndoLog<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UndoLog")
+            .field("edits", &self.edits.len())
+            .field("depth", &self.savepoints.len())
+            .finish()
+    }
+}
+
+// An edit to the arena's values or slots, holding what's needed to reverse it.
+pub(crate) enum Edit<T> {
+    SlotSet(usize, Slot),
+    SlotPushed,
+    SlotPopped(Slot),
+    SlotsReplaced(Vec<Slot>),
+    ValuePushed,
+    ValuePopped(T),
+    ValuesSwapped(usize, usize),
+    ValueSet { pos: usize, id: ArenaId<T>, old: T },
+    ValuesReplaced(Vec<T>),
+}
+
+// Where to return to when rolling back a transaction.
+struct Savepoint {
+    edits: usize,
+    counters: Counters,
+}
+
+// The arena's state that isn't restored by undoing edits, since it is updated
+// directly rather than through the primitives.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Counters {
+    next_uid: u64,
+    first_free: Option<usize>,
+    reserved: usize,
+    // only changed by applying a patch
+    #[cfg(feature = "uuid")]
+    uuid: uuid::Uuid,
+}
+
+impl Counters {
+    pub(crate) fn of<T>(arena: &Arena<T>) -> Self {
+        Self {
+            next_uid: arena.next_uid,
+            first_free: arena.first_free,
+            reserved: arena.reserved,
+            #[cfg(feature = "uuid")]
+            uuid: arena.uuid,
+        }
+    }
+
+    fn restore<T>(self, arena: &mut Arena<T>) {
+        arena.next_uid = self.next_uid;
+        arena.first_free = self.first_free;
+        arena.reserved = self.reserved;
+        #[cfg(feature = "uuid")]
+        {
+            arena.uuid = self.uuid;
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Tests for recording commands on an arena and applying them later.
+
+mod common;
+
+use arena::Arena;
+use common::{run_with, Model, Rng};
+
+/// Records random commands while iterating over the arena, then applies them,
+/// and makes the same changes to the model.
+fn record_and_apply(arena: &mut Arena<u32>, model: &mut Model, rng: &mut Rng, next_val: &mut u32) {
+    // record commands while iterating, including ones on IDs that
+    // were just inserted by the commands
+    let mut commands = arena.commands();
+    let mut recorded = Vec::new();
+    let mut inserted = Vec::new();
+    for (id, val) in arena.pairs_mut() {
+        *next_val += 1;
+        let target = match rng.below(3) {
+            0 if !inserted.is_empty() => inserted[rng.below(inserted.len())],
+            _ => id,
+        };
+        match rng.below(4) {
+            0 => {
+                let new_id = commands.insert(*next_val);
+                inserted.push(new_id);
+                recorded.push((0, new_id, *next_val));
+            }
+            1 => {
+                commands.remove(target);
+                recorded.push((1, target, 0));
+            }
+            2 => {
+                commands.replace(target, *next_val);
+                recorded.push((2, target, *next_val));
+            }
+            _ => {
+                *val = *next_val;
+                model.values.insert(id, *next_val);
+            }
+        }
+    }
+    assert_eq!(commands.len(), recorded.len());
+    arena.apply(commands);
+    for (kind, id, val) in recorded {
+        match kind {
+            0 => model.insert(id, val),
+            1 => {
+                model.remove(id);
+            }
+            _ => {
+                if let Some(old) = model.values.get_mut(&id) {
+                    *old = val;
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn commands_match_model() {
+    for seed in 0..200 {
+        run_with(seed, 100, record_and_apply);
+    }
+}
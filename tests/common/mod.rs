@@ -0,0 +1,364 @@
+//! The reference model and random operations shared by the model-based tests.
+
+#![allow(dead_code)]
+
+use arena::{Arena, ArenaId, Change};
+use std::collections::HashMap;
+
+/// A small deterministic PRNG, so failures can be reproduced from their seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E3779B97F4A7C15) | 1)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// The reference model: values keyed by ID, plus the order the arena should
+/// hold them in, every ID ever issued so stale ones can be checked too, and
+/// the IDs that are reserved but not filled yet.
+#[derive(Default, Clone)]
+pub struct Model {
+    pub values: HashMap<ArenaId<u32>, u32>,
+    pub order: Vec<ArenaId<u32>>,
+    pub issued: Vec<ArenaId<u32>>,
+    pub reserved: Vec<ArenaId<u32>>,
+}
+
+impl Model {
+    pub fn insert(&mut self, id: ArenaId<u32>, val: u32) {
+        assert!(!self.issued.contains(&id), "ID {id:?} was issued twice");
+        self.values.insert(id, val);
+        self.order.push(id);
+        self.issued.push(id);
+    }
+
+    pub fn fill(&mut self, id: ArenaId<u32>, val: u32) {
+        self.reserved.retain(|&i| i != id);
+        self.values.insert(id, val);
+        self.order.push(id);
+    }
+
+    pub fn remove(&mut self, id: ArenaId<u32>) -> Option<u32> {
+        let val = self.values.remove(&id)?;
+        let pos = self.order.iter().position(|&i| i == id).unwrap();
+        self.order.swap_remove(pos);
+        Some(val)
+    }
+
+    pub fn random_id(&self, rng: &mut Rng) -> Option<ArenaId<u32>> {
+        (!self.issued.is_empty()).then(|| self.issued[rng.below(self.issued.len())])
+    }
+}
+
+/// Makes one random change to the arena, and the same change to the model.
+pub fn step(arena: &mut Arena<u32>, model: &mut Model, rng: &mut Rng, next_val: &mut u32) {
+    let len = model.order.len();
+    match rng.below(27) {
+        0..=4 => {
+            *next_val += 1;
+            let id = arena.insert(*next_val);
+            model.insert(id, *next_val);
+        }
+        5..=6 => {
+            *next_val += 1;
+            let mut passed = None;
+            let id = arena.insert_with(|id| {
+                passed = Some(id);
+                *next_val
+            });
+            assert_eq!(passed, Some(id));
+            model.insert(id, *next_val);
+        }
+        7..=9 => {
+            if let Some(id) = model.random_id(rng) {
+                let mut mirror = arena.as_slice().to_vec();
+                let removed = arena.remove_with(id, |i, j| mirror.swap(i, j));
+                mirror.truncate(arena.len());
+                assert_eq!(removed, model.remove(id));
+                assert_eq!(mirror, arena.as_slice());
+            }
+        }
+        10..=11 => {
+            let index = rng.below(len + 2);
+            let expected = model.order.get(index).copied();
+            assert_eq!(
+                arena.remove_at(index),
+                expected.and_then(|id| model.remove(id))
+            );
+        }
+        12 => {
+            let expected = model.order.last().copied();
+            assert_eq!(arena.pop(), expected.and_then(|id| model.remove(id)));
+        }
+        13..=14 => {
+            if len > 0 {
+                let (i, j) = (rng.below(len), rng.below(len));
+                arena.swap(i, j);
+                model.order.swap(i, j);
+            }
+        }
+        15..=16 => {
+            if let (Some(a), Some(b)) = (model.random_id(rng), model.random_id(rng)) {
+                let positions = model
+                    .order
+                    .iter()
+                    .position(|&i| i == a)
+                    .zip(model.order.iter().position(|&i| i == b));
+                assert_eq!(arena.swap_positions(a, b), positions.is_some());
+                if let Some((i, j)) = positions {
+                    model.order.swap(i, j);
+                }
+            }
+        }
+        17 => {
+            let reverse = rng.below(2) == 0;
+            let mut mirror = arena.as_slice().to_vec();
+            arena.sort_by_with(
+                |a, b| if reverse { b.cmp(a) } else { a.cmp(b) },
+                |i, j| mirror.swap(i, j),
+            );
+            assert_eq!(mirror, arena.as_slice());
+            let values = &model.values;
+            model.order.sort_by_key(|id| values[id]);
+            if reverse {
+                model.order.reverse();
+            }
+        }
+        18 => {
+            if rng.below(4) == 0 {
+                arena.clear();
+                model.values.clear();
+                model.order.clear();
+            }
+        }
+        19 => {
+            let vals: Vec<u32> = (0..rng.below(4))
+                .map(|_| {
+                    *next_val += 1;
+                    *next_val
+                })
+                .collect();
+            let ids = arena.extend_with_ids(vals.clone());
+            assert_eq!(ids.len(), vals.len());
+            for (id, val) in ids.into_iter().zip(vals) {
+                model.insert(id, val);
+            }
+        }
+        20 => {
+            let ids: Vec<_> = (0..rng.below(6))
+                .filter_map(|_| model.random_id(rng))
+                .collect();
+            let expected: Vec<_> = ids.iter().map(|id| model.values.remove(id)).collect();
+            model.order.retain(|id| model.values.contains_key(id));
+            let count = expected.iter().flatten().count();
+            let mut mirror = arena.as_slice().to_vec();
+            match rng.below(4) {
+                0 => {
+                    assert_eq!(arena.remove_many(&ids), expected);
+                    mirror = arena.as_slice().to_vec();
+                }
+                1 => {
+                    let removed = arena.remove_many_with(&ids, |i, j| mirror.swap(i, j));
+                    assert_eq!(removed, expected);
+                }
+                2 => {
+                    assert_eq!(arena.delete_many(&ids), count);
+                    mirror = arena.as_slice().to_vec();
+                }
+                _ => {
+                    let deleted = arena.delete_many_with(&ids, |i, j| mirror.swap(i, j));
+                    assert_eq!(deleted, count);
+                }
+            }
+            mirror.truncate(arena.len());
+            assert_eq!(mirror, arena.as_slice());
+        }
+        21 => {
+            let id = arena.reserve_id();
+            assert!(!model.issued.contains(&id), "ID {id:?} was issued twice");
+            model.issued.push(id);
+            model.reserved.push(id);
+        }
+        22 => {
+            if let Some(id) = model.random_id(rng) {
+                let reserved = model.reserved.contains(&id);
+                *next_val += 1;
+                if rng.below(3) == 0 {
+                    assert_eq!(arena.cancel_reservation(id), reserved);
+                    model.reserved.retain(|&i| i != id);
+                } else if reserved {
+                    assert_eq!(arena.fill(id, *next_val), Ok(()));
+                    model.fill(id, *next_val);
+                } else {
+                    assert_eq!(arena.fill(id, *next_val), Err(*next_val));
+                }
+            }
+        }
+        23 => {
+            if let Some(id) = model.random_id(rng) {
+                *next_val += 1;
+                let old = model
+                    .values
+                    .get_mut(&id)
+                    .map(|val| std::mem::replace(val, *next_val));
+                assert_eq!(arena.replace(id, *next_val), old);
+            }
+        }
+        24 => {
+            if let Some(id) = model.random_id(rng) {
+                let new_id = arena.reissue(id);
+                assert_eq!(new_id.is_some(), model.values.contains_key(&id));
+                if let Some(new_id) = new_id {
+                    assert!(
+                        !model.issued.contains(&new_id),
+                        "ID {new_id:?} was issued twice"
+                    );
+                    model.issued.push(new_id);
+                    let val = model.values.remove(&id).unwrap();
+                    model.values.insert(new_id, val);
+                    let pos = model.order.iter().position(|&i| i == id).unwrap();
+                    model.order[pos] = new_id;
+                }
+            }
+        }
+        25 => {
+            let modulus = rng.below(4) as u32 + 2;
+            let mut mirror = arena.as_slice().to_vec();
+            arena.retain_with(|val| val % modulus != 0, |i, j| mirror.swap(i, j));
+            mirror.truncate(arena.len());
+            assert_eq!(mirror, arena.as_slice());
+            let values = &mut model.values;
+            values.retain(|_, val| *val % modulus != 0);
+            model.order.retain(|id| values.contains_key(id));
+        }
+        _ => {
+            if rng.below(8) == 0 {
+                arena.clear_all();
+                model.values.clear();
+                model.order.clear();
+                model.reserved.clear();
+            }
+        }
+    }
+}
+
+/// Checks that the arena holds the same values as the model, in the same order.
+pub fn check(arena: &Arena<u32>, model: &Model) {
+    assert_eq!(arena.check_invariants(), Ok(()));
+    assert_eq!(arena.len(), model.order.len());
+    assert_eq!(arena.is_empty(), model.order.is_empty());
+
+    // every ID ever issued, live or stale, resolves the same way as in the model
+    for &id in &model.issued {
+        assert_eq!(arena.get(id), model.values.get(&id), "get({id:?})");
+        assert_eq!(arena.contains(id), model.values.contains_key(&id));
+        assert_eq!(arena.is_reserved(id), model.reserved.contains(&id));
+    }
+
+    // live values are in the expected positions
+    for (pos, &id) in model.order.iter().enumerate() {
+        assert_eq!(arena.id_at(pos), Some(id));
+        assert_eq!(arena.index_of(id), Some(pos));
+        assert_eq!(arena.as_slice()[pos], model.values[&id]);
+    }
+    assert_eq!(arena.id_at(model.order.len()), None);
+
+    let pairs: Vec<_> = arena.pairs().map(|(id, val)| (id, *val)).collect();
+    let expected: Vec<_> = model
+        .order
+        .iter()
+        .map(|&id| (id, model.values[&id]))
+        .collect();
+    assert_eq!(pairs, expected);
+    assert_eq!(arena.pairs().len(), expected.len());
+    assert!(arena
+        .pairs()
+        .rev()
+        .map(|(id, val)| (id, *val))
+        .eq(expected.iter().rev().copied()));
+    assert!(arena.clone().into_pairs().eq(expected.iter().copied()));
+    assert!(arena.into_iter().eq(expected.iter().map(|(_, val)| val)));
+
+    let mid = expected.len() / 2;
+    assert!(arena
+        .pairs_range(mid..)
+        .map(|(id, val)| (id, *val))
+        .eq(expected[mid..].iter().copied()));
+    assert!(arena
+        .ids_range(..mid)
+        .eq(model.order[..mid].iter().copied()));
+}
+
+/// Replays the arena's recorded changes onto a map of each ID's position, like
+/// a replica of the arena would, and checks that it ends up in sync.
+pub fn check_changes(
+    arena: &mut Arena<u32>,
+    model: &Model,
+    replica: &mut HashMap<ArenaId<u32>, usize>,
+) {
+    for change in arena.drain_changes() {
+        match change {
+            Change::Inserted(id) => {
+                let pos = replica.len();
+                assert_eq!(replica.insert(id, pos), None);
+            }
+            Change::Removed(id) => assert!(replica.remove(&id).is_some()),
+            Change::Moved { id, from, to } => {
+                assert_eq!(replica.insert(id, to), Some(from));
+            }
+            Change::Reissued { old, new } => {
+                let pos = replica.remove(&old).unwrap();
+                assert_eq!(replica.insert(new, pos), None);
+            }
+            Change::Modified(id) => assert!(replica.contains_key(&id)),
+        }
+    }
+    assert_eq!(replica.len(), model.order.len());
+    for (pos, id) in model.order.iter().enumerate() {
+        assert_eq!(replica.get(id), Some(&pos));
+    }
+}
+
+/// Drives an arena with random steps, making the edit under test instead of
+/// one in four of them, sometimes inside a transaction that is rolled back,
+/// and checks the arena and its recorded changes against the model.
+pub fn run_with(
+    seed: u64,
+    steps: usize,
+    edit: fn(&mut Arena<u32>, &mut Model, &mut Rng, &mut u32),
+) {
+    let mut rng = Rng::new(seed);
+    let mut arena = Arena::new();
+    let mut model = Model::default();
+    let mut next_val = 0;
+    let mut replica = HashMap::new();
+    arena.record_changes(true);
+    for _ in 0..steps {
+        if rng.below(4) != 0 {
+            step(&mut arena, &mut model, &mut rng, &mut next_val);
+        } else if rng.below(4) != 0 {
+            edit(&mut arena, &mut model, &mut rng, &mut next_val);
+        } else {
+            let before = arena.clone();
+            let mut tx = arena.begin_transaction();
+            edit(&mut tx, &mut model.clone(), &mut rng, &mut next_val);
+            tx.rollback();
+            assert!(arena.ids_by_slot().eq(before.ids_by_slot()));
+            assert_eq!(arena.clone().insert(0), before.clone().insert(0));
+        }
+        check(&arena, &model);
+        check_changes(&mut arena, &model, &mut replica);
+    }
+}
@@ -0,0 +1,50 @@
+//! Tests for reserving IDs in a concurrent arena from many threads.
+
+mod common;
+
+use arena::ConcurrentArena;
+use common::{step, Model, Rng};
+
+#[test]
+fn concurrent_reservations_match_sequential() {
+    for seed in 0..50 {
+        let mut rng = Rng::new(seed);
+        let mut arena = ConcurrentArena::new();
+        let mut model = Model::default();
+        let mut next_val = 0;
+        for _ in 0..3 {
+            for _ in 0..rng.below(100) {
+                step(&mut arena, &mut model, &mut rng, &mut next_val);
+            }
+            // flushing collects the free slots ahead of the reservations,
+            // otherwise the first reservation does
+            if rng.below(2) == 0 {
+                arena.flush();
+            }
+
+            // the same IDs are reserved as one thread would, in some order
+            let mut sequential = (*arena).clone();
+            let mut expected: Vec<_> = (0..64).map(|_| sequential.reserve_id()).collect();
+            let mut ids: Vec<_> = std::thread::scope(|s| {
+                let workers: Vec<_> = (0..4)
+                    .map(|_| s.spawn(|| (0..16).map(|_| arena.reserve_id()).collect::<Vec<_>>()))
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|w| w.join().unwrap())
+                    .collect()
+            });
+            assert_eq!(arena.pending_count(), 64);
+            ids.sort();
+            expected.sort();
+            assert_eq!(ids, expected);
+
+            arena.flush();
+            assert_eq!(arena.check_invariants(), Ok(()));
+            assert!(ids.iter().all(|&id| arena.is_reserved(id)));
+            assert_eq!((*arena).clone().insert(0), sequential.insert(0));
+            model.issued.extend_from_slice(&ids);
+            model.reserved.extend_from_slice(&ids);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Model-based tests for the copy-on-write arena and its snapshots.
+
+mod common;
+
+use arena::{CowArena, CowSnapshot};
+use common::{Model, Rng};
+
+fn check_cow(arena: &CowSnapshot<u32>, model: &Model) {
+    assert_eq!(arena.len(), model.order.len());
+    for &id in &model.issued {
+        assert_eq!(arena.get(id), model.values.get(&id), "get({id:?})");
+    }
+    for (pos, &id) in model.order.iter().enumerate() {
+        assert_eq!(arena.id_at(pos), Some(id));
+    }
+    assert!(arena
+        .iter()
+        .eq(model.order.iter().map(|id| &model.values[id])));
+    assert_eq!(arena.iter().len(), model.order.len());
+}
+
+/// Drives a copy-on-write arena with random operations, taking snapshots along
+/// the way and checking that they never see the changes made after them.
+fn run_cow(seed: u64, steps: usize) {
+    let mut rng = Rng::new(seed);
+    let mut arena = CowArena::new();
+    let mut model = Model::default();
+    let mut snapshots: Vec<(CowSnapshot<u32>, Model)> = Vec::new();
+    for n in 0..steps as u32 {
+        match rng.below(8) {
+            0..=3 => {
+                let id = arena.insert(n);
+                model.insert(id, n);
+            }
+            4..=5 => {
+                if let Some(id) = model.random_id(&mut rng) {
+                    assert_eq!(arena.remove(id), model.remove(id));
+                }
+            }
+            6 => {
+                if let Some(id) = model.random_id(&mut rng) {
+                    if let Some(val) = arena.get_mut(id) {
+                        *val = n;
+                        model.values.insert(id, n);
+                    }
+                }
+            }
+            _ => {
+                if snapshots.len() == 4 {
+                    snapshots.remove(rng.below(4));
+                }
+                snapshots.push((arena.snapshot(), model.clone()));
+            }
+        }
+        check_cow(&arena, &model);
+        for (snapshot, model) in &snapshots {
+            check_cow(snapshot, model);
+        }
+    }
+}
+
+#[test]
+fn cow_matches_model() {
+    for seed in 0..30 {
+        run_cow(seed, 400);
+    }
+}
@@ -0,0 +1,83 @@
+//! Tests for walking an arena with a cursor while editing it.
+
+mod common;
+
+use arena::Arena;
+use common::{run_with, Model, Rng};
+
+/// Walks the arena with a cursor in a random direction, making random edits
+/// through it, and makes the same edits to the model.
+fn walk(arena: &mut Arena<u32>, model: &mut Model, rng: &mut Rng, next_val: &mut u32) {
+    // walk the arena with a cursor while editing it, which must visit
+    // every value exactly once, except as documented for swaps
+    let forward = rng.below(2) == 0;
+    let mut expected = model.order.clone();
+    let mut visited = Vec::new();
+    let mut cursor = arena.cursor_mut();
+    if !forward {
+        while cursor.move_next() {}
+    }
+    loop {
+        if !forward && !cursor.move_prev() {
+            break;
+        }
+        let pos = cursor.index();
+        let Some((id, val)) = cursor.current() else {
+            break;
+        };
+        assert_eq!(model.order[pos], id);
+        assert_eq!(model.values[&id], *val);
+        visited.push(id);
+        let mut advance = true;
+        match rng.below(4) {
+            0 => {
+                assert_eq!(cursor.remove_current(), model.remove(id));
+                advance = false;
+            }
+            1 => {
+                *next_val += 1;
+                let new_id = cursor.insert(*next_val);
+                model.insert(new_id, *next_val);
+                if forward {
+                    expected.push(new_id);
+                }
+            }
+            2 => {
+                let other = rng.below(model.order.len());
+                assert!(cursor.swap_with(model.order[other]));
+                // swapping with a value the walk hasn't reached yet
+                // moves the current value there to be visited again,
+                // and skips the other value
+                if (forward && other > pos) || (!forward && other < pos) {
+                    expected.push(id);
+                    let skipped = model.order[other];
+                    let i = expected.iter().position(|&e| e == skipped).unwrap();
+                    expected.swap_remove(i);
+                }
+                model.order.swap(pos, other);
+                assert_eq!(cursor.current().unwrap().0, model.order[pos]);
+            }
+            _ => {
+                *next_val += 1;
+                *val = *next_val;
+                model.values.insert(id, *next_val);
+            }
+        }
+        if forward && advance {
+            cursor.move_next();
+        }
+    }
+    if forward {
+        assert_eq!(cursor.current(), None);
+    }
+    expected.sort();
+    visited.sort();
+    assert_eq!(visited, expected);
+}
+
+#[test]
+fn cursor_matches_model() {
+    for seed in 0..200 {
+        run_with(seed, 100, walk);
+    }
+}
@@ -0,0 +1,97 @@
+//! Loom models of the sharded arena's concurrent paths, which check every
+//! interleaving of the threads involved. Run them with:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release --test loom
+//! ```
+
+#![cfg(loom)]
+
+use arena::ShardedArena;
+use loom::sync::Arc;
+use loom::thread;
+
+#[test]
+fn concurrent_inserts_get_distinct_ids() {
+    loom::model(|| {
+        let arena = Arc::new(ShardedArena::with_shards(2));
+        let other = Arc::clone(&arena);
+        let worker = thread::spawn(move || other.insert('A'));
+        let b = arena.insert('B');
+        let a = worker.join().unwrap();
+
+        assert_ne!(a, b);
+        assert_eq!(*arena.get(a).unwrap(), 'A');
+        assert_eq!(*arena.get(b).unwrap(), 'B');
+        assert_eq!(arena.len(), 2);
+    });
+}
+
+#[test]
+fn concurrent_inserts_into_one_shard() {
+    loom::model(|| {
+        let arena = Arc::new(ShardedArena::with_shards(1));
+        let other = Arc::clone(&arena);
+        let worker = thread::spawn(move || other.insert('A'));
+        let b = arena.insert('B');
+        let a = worker.join().unwrap();
+
+        assert_ne!(a, b);
+        assert_eq!(arena.remove(a), Some('A'));
+        assert_eq!(arena.remove(b), Some('B'));
+        assert!(arena.is_empty());
+    });
+}
+
+#[test]
+fn concurrent_removes_only_succeed_once() {
+    loom::model(|| {
+        let arena = Arc::new(ShardedArena::with_shards(2));
+        let a = arena.insert('A');
+        let other = Arc::clone(&arena);
+        let worker = thread::spawn(move || other.remove(a));
+        let removed = arena.remove(a);
+        let other_removed = worker.join().unwrap();
+
+        assert_eq!(removed.xor(other_removed), Some('A'));
+        assert!(!arena.contains(a));
+    });
+}
+
+#[test]
+fn remove_while_inserting() {
+    loom::model(|| {
+        let arena = Arc::new(ShardedArena::with_shards(2));
+        let a = arena.insert('A');
+        let other = Arc::clone(&arena);
+        let worker = thread::spawn(move || other.insert('B'));
+        assert_eq!(arena.remove(a), Some('A'));
+        let b = worker.join().unwrap();
+
+        // the freed slot may be reused, but never with the same ID
+        assert_ne!(a, b);
+        assert_eq!(arena.get(a).map(|v| *v), None);
+        assert_eq!(*arena.get(b).unwrap(), 'B');
+    });
+}
+
+#[test]
+fn get_mut_while_removing() {
+    loom::model(|| {
+        let arena = Arc::new(ShardedArena::with_shards(1));
+        let a = arena.insert(1);
+        let b = arena.insert(2);
+        let other = Arc::clone(&arena);
+        let worker = thread::spawn(move || {
+            if let Some(mut val) = other.get_mut(b) {
+                *val += 10;
+            }
+        });
+
+        // removing `a` moves `b` within the shard, which must not be observed
+        // half way through by the other thread
+        assert_eq!(arena.remove(a), Some(1));
+        worker.join().unwrap();
+        assert_eq!(*arena.get(b).unwrap(), 12);
+    });
+}
@@ -0,0 +1,194 @@
+//! Model-based tests that drive an arena with random operations and compare it
+//! against a simple reference model after every step.
+
+mod common;
+
+use arena::{Arena, HistoryArena};
+use common::{check, check_changes, step, Model, Rng};
+use std::collections::HashMap;
+
+fn check_ids(arena: &Arena<u32>, model: &Model) {
+    let ids: Vec<_> = arena.ids().collect();
+    assert_eq!(ids, model.order);
+    assert_eq!(arena.ids().len(), model.order.len());
+    assert!(arena.ids().rev().eq(model.order.iter().rev().copied()));
+
+    let mut by_slot = model.order.clone();
+    by_slot.sort_by_key(|id| id.slot_index());
+    assert!(arena.ids_by_slot().eq(by_slot.iter().copied()));
+    assert_eq!(arena.ids_by_slot().len(), model.order.len());
+    assert!(arena.ids_by_slot().rev().eq(by_slot.iter().rev().copied()));
+}
+
+fn check_chunks(arena: &mut Arena<u32>, model: &Model) {
+    let expected: Vec<_> = model
+        .order
+        .iter()
+        .map(|&id| (id, model.values[&id]))
+        .collect();
+    // zip stops at the shorter side, so the chunk counts are checked first
+    let chunk_count = expected.chunks(3).len();
+    assert_eq!(arena.chunks_pairs_mut(3).count(), chunk_count);
+    assert_eq!(arena.chunks_pairs_mut(3).len(), chunk_count);
+    for (chunk, expected) in arena.chunks_pairs_mut(3).zip(expected.chunks(3)) {
+        assert!(chunk
+            .map(|(id, val)| (id, *val))
+            .eq(expected.iter().copied()));
+    }
+}
+
+fn check_snapshot(arena: &Arena<u32>, model: &Model) {
+    let mut bytes = Vec::new();
+    arena.write_snapshot(&mut bytes).unwrap();
+    let loaded: Arena<u32> = Arena::read_snapshot(bytes.as_slice()).unwrap();
+    check(&loaded, model);
+    assert_eq!(loaded.free_slot_count(), arena.free_slot_count());
+}
+
+/// Runs a few steps inside a transaction. If it's rolled back, checks that the
+/// arena is exactly as it was, down to the next ID it will assign.
+fn check_transaction(arena: &mut Arena<u32>, model: &mut Model, rng: &mut Rng, next_val: &mut u32) {
+    let before = arena.clone();
+    let saved = model.clone();
+    let mut tx = arena.begin_transaction();
+    for _ in 0..rng.below(8) {
+        step(&mut tx, model, rng, next_val);
+    }
+    if rng.below(4) == 0 {
+        tx.commit();
+        return;
+    }
+    tx.rollback();
+
+    // IDs issued during the transaction are stale again, and will be reissued
+    for id in &model.issued[saved.issued.len()..] {
+        assert_eq!(arena.get(*id), None);
+        assert!(!arena.is_reserved(*id));
+    }
+    *model = saved;
+    check(arena, model);
+    assert!(arena.ids_by_slot().eq(before.ids_by_slot()));
+    assert_eq!(arena.free_slot_count(), before.free_slot_count());
+    assert_eq!(arena.clone().insert(0), before.clone().insert(0));
+    assert_eq!(arena.clone().reserve_id(), before.clone().reserve_id());
+}
+
+/// Patches an older copy of the arena up to date, and checks that the copy
+/// ends up identical, down to the next ID it will assign.
+fn check_patch(arena: &Arena<u32>, model: &Model, base: &Arena<u32>) {
+    let patch = base.diff(arena);
+    #[cfg(feature = "serde")]
+    let patch = {
+        let json = serde_json::to_string(&patch).unwrap();
+        serde_json::from_str(&json).unwrap()
+    };
+    let mut patched = base.clone();
+    patched.apply_patch(patch).unwrap();
+    check(&patched, model);
+    let mut replica = base.ids().enumerate().map(|(pos, id)| (id, pos)).collect();
+    check_changes(&mut patched, model, &mut replica);
+    assert!(patched.ids_by_slot().eq(arena.ids_by_slot()));
+    assert_eq!(patched.free_slot_count(), arena.free_slot_count());
+    assert_eq!(patched.clone().insert(0), arena.clone().insert(0));
+    assert_eq!(patched.reserve_id(), arena.clone().reserve_id());
+}
+
+fn run(seed: u64, steps: usize, check_ids_too: bool) {
+    let mut rng = Rng::new(seed);
+    let mut arena = Arena::new();
+    let mut model = Model::default();
+    let mut next_val = 0;
+    let mut replica = HashMap::new();
+    arena.record_changes(true);
+    let mut base = arena.clone();
+    for _ in 0..steps {
+        if rng.below(32) == 0 {
+            check_transaction(&mut arena, &mut model, &mut rng, &mut next_val);
+        } else {
+            step(&mut arena, &mut model, &mut rng, &mut next_val);
+        }
+        check(&arena, &model);
+        check_chunks(&mut arena, &model);
+        check_changes(&mut arena, &model, &mut replica);
+        if rng.below(16) == 0 {
+            check_snapshot(&arena, &model);
+        }
+        if rng.below(8) == 0 {
+            check_patch(&arena, &model, &base);
+            if rng.below(2) == 0 {
+                base = arena.clone();
+            }
+        }
+        if check_ids_too {
+            check_ids(&arena, &model);
+        }
+    }
+}
+
+#[test]
+fn arena_matches_model() {
+    for seed in 0..200 {
+        run(seed, 300, false);
+    }
+}
+
+#[test]
+fn ids_match_model() {
+    for seed in 0..200 {
+        run(seed, 300, true);
+    }
+}
+
+/// Makes random steps in a history, undoing and redoing them at random, and
+/// checks that every state matches the model it had at that point.
+fn run_history(seed: u64, steps: usize) {
+    let mut rng = Rng::new(seed);
+    let mut history = HistoryArena::with_max_steps(8);
+    let mut model = Model::default();
+    let mut next_val = 0;
+    let mut past: Vec<Model> = Vec::new();
+    let mut future: Vec<Model> = Vec::new();
+    for n in 0..steps {
+        match rng.below(4) {
+            0 => match past.pop() {
+                Some(prev) => {
+                    assert!(history.undo().is_some());
+                    future.push(std::mem::replace(&mut model, prev));
+                }
+                None => assert_eq!(history.undo(), None),
+            },
+            1 => match future.pop() {
+                Some(next) => {
+                    assert!(history.redo().is_some());
+                    past.push(std::mem::replace(&mut model, next));
+                }
+                None => assert_eq!(history.redo(), None),
+            },
+            _ => {
+                let prev = model.clone();
+                let name = format!("step {n}");
+                history.edit(name.clone(), |arena| {
+                    for _ in 0..=rng.below(3) {
+                        step(arena, &mut model, &mut rng, &mut next_val);
+                    }
+                });
+                // steps that don't change the arena aren't recorded
+                if history.undo_name() == Some(&name) {
+                    past.push(prev);
+                    if past.len() > history.max_steps() {
+                        past.remove(0);
+                    }
+                    future.clear();
+                }
+            }
+        }
+        check(&history, &model);
+    }
+}
+
+#[test]
+fn history_matches_model() {
+    for seed in 0..200 {
+        run_history(seed, 300);
+    }
+}
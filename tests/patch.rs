@@ -0,0 +1,139 @@
+//! Tests for diffing arenas and applying the patches.
+
+mod common;
+
+use arena::{Arena, ArenaId, Change};
+use common::Rng;
+use std::collections::HashMap;
+
+fn edit(arena: &mut Arena<u32>, reserved: &mut Vec<ArenaId<u32>>, rng: &mut Rng) {
+    let pos = (!arena.is_empty()).then(|| rng.below(arena.len()));
+    match (rng.below(16), pos) {
+        (0..=4, _) => {
+            arena.insert(rng.below(100) as u32);
+        }
+        (5..=7, Some(pos)) => {
+            arena.remove_at(pos);
+        }
+        (8..=9, Some(pos)) => {
+            let id = arena.id_at(pos).unwrap();
+            arena[id] = rng.below(100) as u32;
+        }
+        (10, Some(pos)) => arena.swap(pos, rng.below(arena.len())),
+        (11, _) => arena.sort(),
+        (12, _) => reserved.push(arena.reserve_id()),
+        (13, _) if !reserved.is_empty() => {
+            let id = reserved.swap_remove(rng.below(reserved.len()));
+            match rng.below(2) {
+                0 => arena.fill(id, rng.below(100) as u32).unwrap(),
+                _ => assert!(arena.cancel_reservation(id)),
+            }
+        }
+        (14, _) if rng.below(8) == 0 => {
+            arena.clear_all();
+            reserved.clear();
+        }
+        _ => {}
+    }
+}
+
+fn assert_same(arena: &Arena<u32>, other: &Arena<u32>) {
+    assert_eq!(arena.check_invariants(), Ok(()));
+    assert_eq!(arena.as_slice(), other.as_slice());
+    assert!(arena.ids().eq(other.ids()));
+    assert!(arena.ids_by_slot().eq(other.ids_by_slot()));
+    assert_eq!(arena.free_slot_count(), other.free_slot_count());
+    assert_eq!(arena.clone().insert(0), other.clone().insert(0));
+    assert_eq!(arena.clone().reserve_id(), other.clone().reserve_id());
+}
+
+// replays the changes logged since `old` onto its positions, which must then
+// match the arena's
+fn check_changes(arena: &mut Arena<u32>, old: &Arena<u32>) {
+    let mut replica: HashMap<_, _> = old.ids().enumerate().map(|(pos, id)| (id, pos)).collect();
+    for change in arena.drain_changes() {
+        match change {
+            Change::Inserted(id) => {
+                let pos = replica.len();
+                assert_eq!(replica.insert(id, pos), None);
+            }
+            Change::Removed(id) => assert!(replica.remove(&id).is_some()),
+            Change::Moved { id, from, to } => assert_eq!(replica.insert(id, to), Some(from)),
+            Change::Reissued { .. } => unreachable!(),
+            Change::Modified(id) => assert!(replica.contains_key(&id)),
+        }
+    }
+    assert_eq!(replica.len(), arena.len());
+    for (pos, id) in arena.ids().enumerate() {
+        assert_eq!(replica.get(&id), Some(&pos));
+    }
+}
+
+#[test]
+fn patch_between_diverged_arenas() {
+    // patches are applied in place, so patch arenas that diverged from a common
+    // base in both directions, and roll them back again
+    for seed in 0..200 {
+        let mut rng = Rng::new(seed);
+        let mut base = Arena::new();
+        let mut reserved = Vec::new();
+        for _ in 0..rng.below(40) {
+            edit(&mut base, &mut reserved, &mut rng);
+        }
+        let mut arenas = [base.clone(), base.clone(), base];
+        for arena in &mut arenas[..2] {
+            let mut reserved = reserved.clone();
+            for _ in 0..rng.below(40) {
+                edit(arena, &mut reserved, &mut rng);
+            }
+        }
+
+        for (from, to) in [(0, 1), (1, 0), (2, 0), (0, 2), (2, 1)] {
+            let (from, to) = (&arenas[from], &arenas[to]);
+            let mut patched = from.clone();
+            patched.record_changes(true);
+            patched.apply_patch(from.diff(to)).unwrap();
+            assert_same(&patched, to);
+            check_changes(&mut patched, from);
+
+            let mut patched = from.clone();
+            let mut tx = patched.begin_transaction();
+            tx.apply_patch(from.diff(to)).unwrap();
+            tx.rollback();
+            assert_same(&patched, from);
+        }
+
+        // applied to the wrong arena, a patch is either rejected without
+        // changing the arena, or makes a consistent one
+        for (from, to, wrong) in [(0, 1, 2), (2, 0, 1), (1, 2, 0)] {
+            let patch = arenas[from].diff(&arenas[to]);
+            let mut patched = arenas[wrong].clone();
+            match patched.apply_patch(patch) {
+                Ok(()) => assert_eq!(patched.check_invariants(), Ok(())),
+                Err(_) => assert_same(&patched, &arenas[wrong]),
+            }
+        }
+    }
+}
+
+#[test]
+fn small_patch_only_touches_what_it_changes() {
+    let mut base: Arena<u32> = (0..1000).collect();
+    let ids: Vec<_> = base.ids().collect();
+    base.remove(ids[10]);
+    let mut arena = base.clone();
+    arena[ids[500]] = 7;
+    arena.remove(ids[100]);
+    arena.insert(9);
+
+    // every edit to the arena is recorded in the transaction's undo log
+    let mut copy = base.clone();
+    let mut tx = copy.begin_transaction();
+    tx.apply_patch(base.diff(&arena)).unwrap();
+    let debug = format!("{:?}", &*tx);
+    let edits = debug.split("edits: ").nth(1).unwrap();
+    let edits: usize = edits[..edits.find(',').unwrap()].parse().unwrap();
+    assert!(edits <= 10, "{debug}");
+    tx.commit();
+    assert_same(&copy, &arena);
+}
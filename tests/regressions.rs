@@ -0,0 +1,296 @@
+//! Regression tests for bugs that were found in the arena, each reproducing the
+//! case that used to fail.
+
+use arena::{Arena, HistoryArena};
+use std::cmp::Ordering;
+
+#[test]
+#[allow(clippy::clone_on_copy)]
+fn cloned_id_matches_arena() {
+    // cloning an ID used to give it a new UUID, so the clone never matched
+    let mut arena = Arena::new();
+    let a = arena.insert('A');
+    let cloned = a.clone();
+    assert_eq!(cloned, a);
+    assert_eq!(arena.get(cloned), Some(&'A'));
+}
+
+#[test]
+fn remove_last_then_insert_twice() {
+    // removing the last value used to free its slot twice, making the free
+    // list cyclic so the next two inserts shared a slot
+    let mut arena = Arena::new();
+    arena.insert('A');
+    let b = arena.insert('B');
+    assert_eq!(arena.remove(b), Some('B'));
+
+    let c = arena.insert('C');
+    let d = arena.insert('D');
+    assert_ne!(c, d);
+    assert_eq!(arena.get(c), Some(&'C'));
+    assert_eq!(arena.get(d), Some(&'D'));
+    assert_eq!(arena.slot_count(), 3);
+    assert_eq!(arena.check_invariants(), Ok(()));
+}
+
+#[test]
+fn swap_remapped_values() {
+    // swapping used to write the value slots at the slot indices instead of
+    // the value positions, which only showed once the two differed
+    let mut arena = Arena::new();
+    let a = arena.insert('A');
+    let b = arena.insert('B');
+    let c = arena.insert('C');
+    arena.remove(a);
+    assert_eq!(arena.as_slice(), &['C', 'B']);
+
+    arena.swap(0, 1);
+    assert_eq!(arena.check_invariants(), Ok(()));
+    assert_eq!(arena.as_slice(), &['B', 'C']);
+    assert_eq!(arena.id_at(0), Some(b));
+    assert_eq!(arena.id_at(1), Some(c));
+    assert_eq!(arena.get(b), Some(&'B'));
+    assert_eq!(arena.get(c), Some(&'C'));
+    assert_eq!(arena.remove(b), Some('B'));
+    assert_eq!(arena.as_slice(), &['C']);
+}
+
+#[test]
+fn sort_with_inconsistent_comparator() {
+    // a comparator that isn't a total order used to make the partition return
+    // a pivot before the range, which overflowed or recursed forever
+    let mut arena: Arena<u32> = (0..32).collect();
+    let ids: Vec<_> = (0..32).map(|i| arena.id_at(i).unwrap()).collect();
+    arena.sort_by(|_, _| Ordering::Greater);
+    assert_eq!(arena.check_invariants(), Ok(()));
+
+    let mut state = 1u32;
+    arena.sort_by(|_, _| {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        [Ordering::Less, Ordering::Equal, Ordering::Greater][(state >> 16) as usize % 3]
+    });
+    assert_eq!(arena.check_invariants(), Ok(()));
+    for (value, id) in (0..32).zip(ids) {
+        assert_eq!(arena.get(id), Some(&value));
+    }
+    let mut values = arena.as_slice().to_vec();
+    values.sort();
+    assert!(values.into_iter().eq(0..32));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn seed_rejects_duplicate_ids() {
+    use arena::ArenaSeed;
+    use serde::de::DeserializeSeed;
+
+    // the second entry used to replace the first in the remap, so the first
+    // value was left behind when loading failed
+    let json = r#"{"next_uid":2,"entries":[
+        {"uid":0,"idx":0,"val":"A"},
+        {"uid":0,"idx":0,"val":"B"},
+        {"uid":1,"idx":1,"val":"C"}
+    ]}"#;
+    let mut world = Arena::new();
+    world.insert('X');
+
+    let mut de = serde_json::Deserializer::from_str(json);
+    assert!(ArenaSeed::new(&mut world).deserialize(&mut de).is_err());
+    assert_eq!(world.as_slice(), &['X']);
+    assert_eq!(world.check_invariants(), Ok(()));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn seed_rolls_back_on_invalid_value() {
+    use arena::ArenaSeed;
+    use serde::de::DeserializeSeed;
+
+    let json = r#"{"next_uid":2,"entries":[
+        {"uid":0,"idx":0,"val":"A"},
+        {"uid":1,"idx":1,"val":7}
+    ]}"#;
+    let mut world = Arena::new();
+    world.insert('X');
+
+    let mut de = serde_json::Deserializer::from_str(json);
+    assert!(ArenaSeed::new(&mut world).deserialize(&mut de).is_err());
+    assert_eq!(world.as_slice(), &['X']);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize_keeps_value_order() {
+    // removing `a` moves `c` into the first position, ahead of `b` even though
+    // its slot comes after, which deserializing used to undo
+    let mut arena = Arena::new();
+    let a = arena.insert('A');
+    arena.insert('B');
+    arena.insert('C');
+    arena.remove(a);
+    assert_eq!(arena.as_slice(), &['C', 'B']);
+
+    let json = serde_json::to_string(&arena).unwrap();
+    let mut loaded: Arena<char> = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.as_slice(), &['C', 'B']);
+    assert_eq!(loaded.check_invariants(), Ok(()));
+
+    // the freed slot is reused, and the round trip is stable
+    loaded.insert('D');
+    assert_eq!(loaded.slot_count(), 3);
+    let json = serde_json::to_string(&loaded).unwrap();
+    let reloaded: Arena<char> = serde_json::from_str(&json).unwrap();
+    assert_eq!(reloaded.as_slice(), &['C', 'B', 'D']);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize_rejects_invalid_entries() {
+    let invalid = [
+        // uid not below next_uid
+        r#"{"next_uid":1,"entries":[{"uid":1,"idx":0,"val":"A"}]}"#,
+        // duplicate uid
+        r#"{"next_uid":2,"entries":[{"uid":0,"idx":0,"val":"A"},{"uid":0,"idx":1,"val":"B"}]}"#,
+        // duplicate slot index
+        r#"{"next_uid":2,"entries":[{"uid":0,"idx":0,"val":"A"},{"uid":1,"idx":0,"val":"B"}]}"#,
+    ];
+    for json in invalid {
+        assert!(serde_json::from_str::<Arena<char>>(json).is_err(), "{json}");
+    }
+}
+
+#[test]
+fn leaked_transaction_is_discarded() {
+    // a leaked guard used to leave its undo log on the arena for good, so
+    // every later edit was recorded into it
+    let mut arena = Arena::new();
+    let mut tx = arena.begin_transaction();
+    tx.insert('A');
+    std::mem::forget(tx);
+    assert!(!format!("{arena:?}").contains("undo: None"));
+
+    // the leaked transaction's changes are kept
+    arena.insert('B');
+    arena.begin_transaction().commit();
+    assert_eq!(arena.as_slice(), &['A', 'B']);
+    assert!(format!("{arena:?}").contains("undo: None"));
+
+    let mut tx = arena.begin_transaction();
+    tx.insert('C');
+    tx.rollback();
+    assert_eq!(arena.as_slice(), &['A', 'B']);
+}
+
+#[test]
+fn leaked_nested_transaction_belongs_to_outer() {
+    let mut arena = Arena::from(['A']);
+    let mut outer = arena.begin_transaction();
+    outer.insert('B');
+    let mut inner = outer.begin_transaction();
+    inner.insert('C');
+    std::mem::forget(inner);
+
+    // the outer transaction rolls back the leaked one's changes with its own
+    outer.insert('D');
+    outer.rollback();
+    assert_eq!(arena.as_slice(), &['A']);
+    assert!(format!("{arena:?}").contains("undo: None"));
+}
+
+#[test]
+fn nested_transaction_in_history_step() {
+    let mut history = HistoryArena::new();
+    history.edit("add A", |tx| {
+        tx.insert('A');
+        let mut inner = tx.begin_transaction();
+        inner.insert('B');
+        inner.rollback();
+    });
+    assert_eq!(history.as_slice(), &['A']);
+    assert_eq!(history.undo(), Some("add A"));
+    assert!(history.is_empty());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn patch_size_follows_changes() {
+    // patches used to hold the whole order of the values and the free list,
+    // so even a one-entry change was as large as the arena
+    let mut base: Arena<u32> = (0..1000).collect();
+    let ids: Vec<_> = base.ids().collect();
+    base.remove(ids[10]);
+    base.remove(ids[20]);
+
+    let mut arena = base.clone();
+    arena[ids[500]] = 7;
+    arena.remove(ids[100]);
+    arena.insert(9);
+    let patch = base.diff(&arena);
+    let json = serde_json::to_string(&patch).unwrap();
+    assert!(json.len() < 300, "{json}");
+
+    let mut copy = base.clone();
+    copy.apply_patch(serde_json::from_str(&json).unwrap())
+        .unwrap();
+    assert_eq!(copy.as_slice(), arena.as_slice());
+    assert_eq!(copy.insert(0), arena.insert(0));
+}
+
+#[cfg(feature = "uuid")]
+#[test]
+#[should_panic(expected = "arena was modified after the command buffer was created")]
+fn commands_applied_to_other_arena() {
+    // another empty arena has the same layout but a different UUID, which
+    // used to pass the check, so that filling the reserved slots failed
+    let arena = Arena::new();
+    let mut commands = arena.commands();
+    commands.insert('A');
+
+    let mut other = Arena::new();
+    other.apply(commands);
+}
+
+#[test]
+fn transaction_begun_through_deref() {
+    // beginning a transaction on the arena a guard dereferences to used to
+    // discard the guard's log, so ending the guard panicked in `drop`
+    let mut arena = Arena::new();
+    let mut outer = arena.begin_transaction();
+    outer.insert('A');
+    let mut inner = Arena::begin_transaction(&mut outer);
+    inner.insert('B');
+    inner.rollback();
+
+    // the outer transaction was ended, keeping its changes
+    outer.insert('C');
+    let mut nested = outer.begin_transaction();
+    nested.insert('D');
+    nested.rollback();
+    drop(outer);
+    assert_eq!(arena.as_slice(), &['A', 'C']);
+    assert!(format!("{arena:?}").contains("undo: None"));
+
+    let mut tx = arena.begin_transaction();
+    tx.insert('E');
+    tx.rollback();
+    assert_eq!(arena.as_slice(), &['A', 'C']);
+}
+
+#[test]
+fn history_step_begun_through_deref() {
+    let mut history = HistoryArena::new();
+    history.edit("add A", |tx| tx.insert('A'));
+    history.edit("add B and C", |tx| {
+        tx.insert('B');
+        let mut inner = Arena::begin_transaction(tx);
+        inner.insert('C');
+        inner.commit();
+    });
+    assert_eq!(history.as_slice(), &['A', 'B', 'C']);
+
+    // the step's transaction was ended, so it couldn't be recorded and the
+    // earlier steps can no longer be undone
+    assert_eq!(history.undo(), None);
+    assert_eq!(history.as_slice(), &['A', 'B', 'C']);
+    assert_eq!(history.check_invariants(), Ok(()));
+}
@@ -0,0 +1,65 @@
+//! Tests for the sharded arena against the reference model.
+
+mod common;
+
+use arena::ShardedArena;
+use common::{Model, Rng};
+use std::collections::HashMap;
+
+#[test]
+fn sharded_matches_model() {
+    for seed in 0..20 {
+        let mut rng = Rng::new(seed);
+        let arena = ShardedArena::with_shards(rng.below(6) + 1);
+        let mut model = Model::default();
+        for n in 0..300 {
+            match rng.below(8) {
+                0..=3 => {
+                    let id = arena.insert(n);
+                    model.insert(id, n);
+                }
+                4..=5 => {
+                    if let Some(id) = model.random_id(&mut rng) {
+                        assert_eq!(arena.remove(id), model.remove(id));
+                    }
+                }
+                6 => {
+                    if let Some(id) = model.random_id(&mut rng) {
+                        if let Some(mut val) = arena.get_mut(id) {
+                            *val = n;
+                            model.values.insert(id, n);
+                        }
+                    }
+                }
+                _ => {
+                    arena.par_for_each_mut(|_, val| *val += 1);
+                    model.values.values_mut().for_each(|val| *val += 1);
+                }
+            }
+
+            assert_eq!(arena.len(), model.values.len());
+            for &id in &model.issued {
+                assert_eq!(
+                    arena.get(id).map(|val| *val),
+                    model.values.get(&id).copied()
+                );
+            }
+            let seen = std::sync::Mutex::new(HashMap::new());
+            arena.par_for_each(|id, val| {
+                assert!(seen.lock().unwrap().insert(id, *val).is_none());
+            });
+            assert_eq!(seen.into_inner().unwrap(), model.values);
+        }
+    }
+}
+
+#[test]
+fn sharded_poisoned_after_panic() {
+    let arena = ShardedArena::with_shards(1);
+    arena.insert(1);
+    let result = std::panic::catch_unwind(|| {
+        arena.par_for_each_mut(|_, _| panic!("interrupted"));
+    });
+    assert!(result.is_err());
+    assert!(std::panic::catch_unwind(|| arena.len()).is_err());
+}
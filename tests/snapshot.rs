@@ -0,0 +1,84 @@
+//! Tests for reading and writing arena snapshots.
+
+use arena::{Arena, ArenaId, SnapshotError, SnapshotValue};
+use std::io::{self, Read, Write};
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    parent: Option<ArenaId<Node>>,
+}
+
+impl SnapshotValue for Node {
+    fn write_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.name.write_value(w)?;
+        self.parent.write_value(w)
+    }
+
+    fn read_value<R: Read>(r: &mut R) -> io::Result<Self> {
+        Ok(Self {
+            name: SnapshotValue::read_value(r)?,
+            parent: SnapshotValue::read_value(r)?,
+        })
+    }
+}
+
+#[test]
+fn ids_in_values_resolve_after_read() {
+    // with the `uuid` feature, IDs held by values used to be loaded with a nil
+    // UUID, so they no longer matched the loaded arena
+    let mut arena = Arena::new();
+    let root = arena.insert(Node {
+        name: String::from("root"),
+        parent: None,
+    });
+    let child = arena.insert(Node {
+        name: String::from("child"),
+        parent: Some(root),
+    });
+
+    let mut bytes = Vec::new();
+    arena.write_snapshot(&mut bytes).unwrap();
+    let loaded: Arena<Node> = Arena::read_snapshot(bytes.as_slice()).unwrap();
+
+    let parent = loaded[child].parent.unwrap();
+    assert_eq!(parent, root);
+    assert_eq!(loaded[parent].name, "root");
+}
+
+#[test]
+fn reads_version_1() {
+    // written by version 1 of the format, before reserved slots were added,
+    // for an arena of "A", "B" and "C" after "A" was removed
+    #[rustfmt::skip]
+    let bytes: &[u8] = &[
+        b'A', b'R', b'N', b'A',
+        1, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        4, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0,
+        3, 0, 0, 0, 0, 0, 0, 0,
+        2, 0, 0, 0, 0, 0, 0, 0,
+        0, 255, 255, 255, 255, 255, 255, 255, 255,
+        1, 2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0,
+        1, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        1, 0, 0, 0, 0, 0, 0, 0, b'C',
+        1, 0, 0, 0, 0, 0, 0, 0, b'B',
+    ];
+
+    let mut loaded: Arena<String> = Arena::read_snapshot(bytes).unwrap();
+    assert_eq!(loaded.as_slice(), &["C", "B"]);
+    assert_eq!(loaded.slot_count(), 3);
+    assert_eq!(loaded.check_invariants(), Ok(()));
+
+    // the free slot is reused
+    let d = loaded.insert(String::from("D"));
+    assert_eq!(loaded.slot_count(), 3);
+    assert_eq!(loaded[d], "D");
+
+    // and truncating it anywhere is reported as such
+    for len in 0..bytes.len() {
+        let res = Arena::<String>::read_snapshot(&bytes[..len]);
+        assert!(matches!(res, Err(SnapshotError::Truncated)), "{len}");
+    }
+}